@@ -0,0 +1,22 @@
+#!/usr/bin/env bash
+set -e
+
+# Runs the SQL regression tests of ./tests against a running server where pg_bestmatch is installed,
+# for example by `cargo pgrx install`. The server is found by PGHOST, PGPORT and PGUSER, and the
+# user must be a superuser. Tests are named as arguments, or all of them run.
+
+PG_CONFIG=${PG_CONFIG:-pg_config}
+PG_REGRESS="$(dirname $($PG_CONFIG --pgxs))/../test/regress/pg_regress"
+if [ "$#" -eq 0 ]; then
+  set -- $(ls ./tests/sql | sed -e 's/\.sql$//')
+fi
+
+mkdir -p ./target/regress
+$PG_REGRESS \
+  --bindir="$($PG_CONFIG --bindir)" \
+  --inputdir=./tests \
+  --outputdir=./target/regress \
+  --dbname=pg_bestmatch_regress \
+  --load-extension=pg_bestmatch \
+  --load-extension=dblink \
+  "$@"
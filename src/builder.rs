@@ -0,0 +1,282 @@
+//! Counting of terms over a corpus in bounded memory. Counts are kept in a hash map until its
+//! estimated size exceeds a limit, and are then spilled to a file sorted by term. The runs are
+//! merged at the end, so the counts come out sorted by term either way.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+
+/// Estimated bytes of a hash map entry besides the heap data of its key.
+const ENTRY_OVERHEAD: usize = 48;
+
+pub trait Key: Ord + Hash + Clone {
+    /// Heap bytes of the key.
+    fn size(&self) -> usize;
+    fn write(&self, w: &mut impl Write) -> io::Result<()>;
+    /// The next key, or `None` at the end of the file.
+    fn read(r: &mut impl Read) -> io::Result<Option<Self>>;
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<Option<u32>> {
+    let mut buf = [0u8; 4];
+    match r.read_exact(&mut buf) {
+        Ok(()) => Ok(Some(u32::from_le_bytes(buf))),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn read_i64(r: &mut impl Read) -> io::Result<i64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(i64::from_le_bytes(buf))
+}
+
+impl Key for String {
+    fn size(&self) -> usize {
+        self.capacity()
+    }
+
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&(self.len() as u32).to_le_bytes())?;
+        w.write_all(self.as_bytes())
+    }
+
+    fn read(r: &mut impl Read) -> io::Result<Option<Self>> {
+        let Some(len) = read_u32(r)? else {
+            return Ok(None);
+        };
+        let mut buf = vec![0u8; len as usize];
+        r.read_exact(&mut buf)?;
+        String::from_utf8(buf)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl Key for i32 {
+    fn size(&self) -> usize {
+        0
+    }
+
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&self.to_le_bytes())
+    }
+
+    fn read(r: &mut impl Read) -> io::Result<Option<Self>> {
+        Ok(read_u32(r)?.map(|x| x as i32))
+    }
+}
+
+/// Occurrences of each term (`cf`) and documents containing it (`df`).
+pub struct Counter<K> {
+    counts: HashMap<K, (i64, i64)>,
+    memory: usize,
+    limit: usize,
+    dir: PathBuf,
+    prefix: String,
+    runs: Vec<PathBuf>,
+}
+
+impl<K: Key> Counter<K> {
+    /// Runs are spilled to `dir`, to files named after `prefix`.
+    pub fn new(limit: usize, dir: PathBuf, prefix: String) -> Counter<K> {
+        Counter {
+            counts: HashMap::new(),
+            memory: 0,
+            limit,
+            dir,
+            prefix,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Adds the terms of a document.
+    pub fn push(&mut self, mut terms: Vec<K>) -> io::Result<()> {
+        terms.sort_unstable();
+        for group in terms.chunk_by(|a, b| a == b) {
+            let cf = group.len() as i64;
+            match self.counts.get_mut(&group[0]) {
+                Some(counts) => {
+                    counts.0 += cf;
+                    counts.1 += 1;
+                }
+                None => {
+                    self.memory += group[0].size() + ENTRY_OVERHEAD;
+                    self.counts.insert(group[0].clone(), (cf, 1));
+                }
+            }
+        }
+        if self.memory > self.limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn sorted(&mut self) -> Vec<(K, i64, i64)> {
+        let mut entries = self
+            .counts
+            .drain()
+            .map(|(key, (cf, df))| (key, cf, df))
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        self.memory = 0;
+        entries
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        let path = self
+            .dir
+            .join(format!("{}.{}", self.prefix, self.runs.len()));
+        let mut w = BufWriter::new(File::create(&path)?);
+        self.runs.push(path);
+        for (key, cf, df) in self.sorted() {
+            key.write(&mut w)?;
+            w.write_all(&cf.to_le_bytes())?;
+            w.write_all(&df.to_le_bytes())?;
+        }
+        w.flush()
+    }
+
+    /// The counts of all documents, sorted by term.
+    pub fn finish(mut self) -> io::Result<Counts<K>> {
+        if self.runs.is_empty() {
+            return Ok(Counts::Memory(self.sorted().into_iter()));
+        }
+        self.spill()?;
+        let mut merge = Merge {
+            readers: Vec::new(),
+            heap: BinaryHeap::new(),
+            heads: Vec::new(),
+        };
+        for path in self.runs.iter() {
+            merge.readers.push(BufReader::new(File::open(path)?));
+            // open files stay readable, and nothing is left behind
+            std::fs::remove_file(path)?;
+        }
+        self.runs.clear();
+        for i in 0..merge.readers.len() {
+            merge.heads.push((0, 0));
+            merge.advance(i)?;
+        }
+        Ok(Counts::Merge(merge))
+    }
+}
+
+impl<K> Drop for Counter<K> {
+    /// Removes the runs of a counter that is not finished, such as one of a failed build.
+    fn drop(&mut self) {
+        for path in self.runs.iter() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+pub struct Merge<K> {
+    readers: Vec<BufReader<File>>,
+    heap: BinaryHeap<Reverse<(K, usize)>>,
+    heads: Vec<(i64, i64)>,
+}
+
+impl<K: Key> Merge<K> {
+    /// Reads the next entry of run `i` into the heap.
+    fn advance(&mut self, i: usize) -> io::Result<()> {
+        let r = &mut self.readers[i];
+        if let Some(key) = K::read(r)? {
+            self.heads[i] = (read_i64(r)?, read_i64(r)?);
+            self.heap.push(Reverse((key, i)));
+        }
+        Ok(())
+    }
+}
+
+pub enum Counts<K> {
+    Memory(std::vec::IntoIter<(K, i64, i64)>),
+    Merge(Merge<K>),
+}
+
+impl<K: Key> Iterator for Counts<K> {
+    type Item = (K, i64, i64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Counts::Memory(entries) => entries.next(),
+            Counts::Merge(merge) => {
+                let Reverse((key, i)) = merge.heap.pop()?;
+                let (mut cf, mut df) = merge.heads[i];
+                if let Err(e) = merge.advance(i) {
+                    pgrx::error!("failed to read spilled counts: {}", e);
+                }
+                // the same term in other runs
+                while matches!(merge.heap.peek(), Some(Reverse((next, _))) if *next == key) {
+                    let Reverse((_, j)) = merge.heap.pop().unwrap();
+                    cf += merge.heads[j].0;
+                    df += merge.heads[j].1;
+                    if let Err(e) = merge.advance(j) {
+                        pgrx::error!("failed to read spilled counts: {}", e);
+                    }
+                }
+                Some((key, cf, df))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Counter;
+
+    fn count<K: super::Key>(
+        limit: usize,
+        name: &str,
+        documents: Vec<Vec<K>>,
+    ) -> Vec<(K, i64, i64)> {
+        let prefix = format!("bm25_test_{}_{}", std::process::id(), name);
+        let mut counter = Counter::new(limit, std::env::temp_dir(), prefix);
+        for terms in documents {
+            counter.push(terms).unwrap();
+        }
+        counter.finish().unwrap().collect()
+    }
+
+    #[test]
+    fn test_counter() {
+        let documents = || {
+            vec![
+                vec!["pie".to_string(), "apple".to_string(), "pie".to_string()],
+                vec!["apple".to_string(), "tart".to_string()],
+                vec![],
+                vec!["cherry".to_string(), "pie".to_string()],
+            ]
+        };
+        let expected = vec![
+            ("apple".to_string(), 2, 2),
+            ("cherry".to_string(), 1, 1),
+            ("pie".to_string(), 3, 2),
+            ("tart".to_string(), 1, 1),
+        ];
+        assert_eq!(count(usize::MAX, "memory", documents()), expected);
+        // a run for each document
+        assert_eq!(count(0, "spill", documents()), expected);
+        assert_eq!(
+            count(0, "ids", vec![vec![7, 3, 7], vec![3], vec![-1]]),
+            vec![(-1, 1, 1), (3, 2, 2), (7, 2, 1)]
+        );
+    }
+
+    #[test]
+    fn test_counter_drop() {
+        let dir = std::env::temp_dir();
+        let prefix = format!("bm25_test_{}_drop", std::process::id());
+        let mut counter = Counter::new(0, dir.clone(), prefix.clone());
+        counter.push(vec![1, 2]).unwrap();
+        counter.push(vec![2, 3]).unwrap();
+        assert!(dir.join(format!("{}.1", prefix)).exists());
+        drop(counter);
+        assert!(!dir.join(format!("{}.0", prefix)).exists());
+        assert!(!dir.join(format!("{}.1", prefix)).exists());
+    }
+}
@@ -0,0 +1,155 @@
+//! Fusion of rankings from different retrievers, e.g. BM25 and dense retrieval. Higher scores are
+//! better, and the results are sorted by fused score, best first.
+
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// `(s - min) / (max - min)`
+    MinMax,
+    /// `(s - mean) / stddev`
+    ZScore,
+}
+
+impl Normalization {
+    pub fn parse(s: &str) -> Result<Normalization, String> {
+        match s {
+            "minmax" => Ok(Normalization::MinMax),
+            "zscore" => Ok(Normalization::ZScore),
+            _ => Err(format!("unknown normalization: {s}")),
+        }
+    }
+
+    fn apply(&self, scores: &[f32]) -> Vec<f32> {
+        if scores.is_empty() {
+            return Vec::new();
+        }
+        let n = scores.len() as f32;
+        let (shift, scale) = match self {
+            Normalization::MinMax => {
+                let min = scores.iter().copied().fold(f32::INFINITY, f32::min);
+                let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                (min, max - min)
+            }
+            Normalization::ZScore => {
+                let mean = scores.iter().sum::<f32>() / n;
+                let variance = scores.iter().map(|s| (s - mean) * (s - mean)).sum::<f32>() / n;
+                (mean, variance.sqrt())
+            }
+        };
+        // all scores are equal, so they tell nothing apart
+        if scale == 0.0 {
+            return vec![0.0; scores.len()];
+        }
+        scores.iter().map(|s| (s - shift) / scale).collect()
+    }
+}
+
+fn sorted(scores: HashMap<&str, f32>) -> Vec<(String, f32)> {
+    let mut result = scores
+        .into_iter()
+        .map(|(id, score)| (id.to_string(), score))
+        .collect::<Vec<_>>();
+    result.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    result
+}
+
+/// Reciprocal rank fusion: each list adds `weight / (k + rank)` to its documents, where ranks
+/// start at 1. A document listed twice in a list counts at its best rank.
+pub fn rrf(lists: &[(&[&str], f32)], k: f32) -> Vec<(String, f32)> {
+    let mut scores = HashMap::<&str, f32>::new();
+    for (ids, weight) in lists.iter() {
+        let mut seen = HashSet::new();
+        for (rank, id) in ids.iter().enumerate() {
+            if seen.insert(*id) {
+                *scores.entry(id).or_default() += weight / (k + (rank + 1) as f32);
+            }
+        }
+    }
+    sorted(scores)
+}
+
+/// Pairs of ids and scores where both are present. A null score is a missing one, like a null id.
+pub fn present<'a>(ids: &'a [Option<String>], scores: &[Option<f32>]) -> (Vec<&'a str>, Vec<f32>) {
+    ids.iter()
+        .zip(scores)
+        .filter_map(|(id, score)| Some((id.as_deref()?, (*score)?)))
+        .unzip()
+}
+
+/// Weighted sum of normalized scores. A document missing from a list gets 0 from it, which is the
+/// lowest score under min-max normalization and the mean under z-score normalization. As in
+/// `rrf`, a document listed twice in a list counts at its best rank, and only that score is
+/// normalized.
+pub fn weighted(
+    lists: &[(&[&str], &[f32], f32)],
+    normalization: Normalization,
+) -> Vec<(String, f32)> {
+    let mut scores = HashMap::<&str, f32>::new();
+    for (ids, raw, weight) in lists.iter() {
+        let mut seen = HashSet::new();
+        let (ids, raw): (Vec<_>, Vec<_>) = ids
+            .iter()
+            .zip(raw.iter())
+            .filter(|(id, _)| seen.insert(**id))
+            .unzip();
+        for (id, score) in ids.into_iter().zip(normalization.apply(&raw)) {
+            *scores.entry(id).or_default() += weight * score;
+        }
+    }
+    sorted(scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Normalization;
+
+    #[test]
+    fn test_rrf() {
+        let bm25 = ["a", "b", "c"];
+        let dense = ["c", "a", "d"];
+        let fused = super::rrf(&[(&bm25, 1.0), (&dense, 1.0)], 60.0);
+        let ids = fused.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["a", "c", "b", "d"]);
+        assert_eq!(fused[0].1, 1.0 / 61.0 + 1.0 / 62.0);
+        let fused = super::rrf(&[(&bm25, 1.0), (&dense, 3.0)], 60.0);
+        assert_eq!(fused[0].0, "c");
+    }
+
+    #[test]
+    fn test_weighted() {
+        let bm25 = ["a", "b", "c"];
+        let dense = ["b", "d"];
+        let fused = super::weighted(
+            &[(&bm25, &[10.0, 6.0, 2.0], 0.5), (&dense, &[0.9, 0.7], 0.5)],
+            Normalization::MinMax,
+        );
+        assert_eq!(
+            fused,
+            vec![
+                ("b".to_string(), 0.75),
+                ("a".to_string(), 0.5),
+                ("c".to_string(), 0.0),
+                ("d".to_string(), 0.0)
+            ]
+        );
+        // the duplicate of `a` neither adds up nor stretches the normalization
+        let fused = super::weighted(
+            &[(&["a", "b", "a"], &[10.0, 6.0, 2.0], 1.0)],
+            Normalization::MinMax,
+        );
+        assert_eq!(fused, vec![("a".to_string(), 1.0), ("b".to_string(), 0.0)]);
+        assert_eq!(Normalization::ZScore.apply(&[1.0, 3.0]), vec![-1.0, 1.0]);
+        assert_eq!(Normalization::MinMax.apply(&[2.0, 2.0]), vec![0.0, 0.0]);
+        assert!(Normalization::parse("l2").is_err());
+    }
+
+    #[test]
+    fn test_present() {
+        let ids = [Some("a".to_string()), None, Some("c".to_string())];
+        assert_eq!(
+            super::present(&ids, &[Some(1.0), Some(2.0), None]),
+            (vec!["a"], vec![1.0])
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use pgrx::guc::{GucContext, GucFlags, GucRegistry, GucSetting};
+use std::ffi::CStr;
+
+pub static MAINTENANCE_NAPTIME: GucSetting<i32> = GucSetting::<i32>::new(60);
+
+pub static MAINTENANCE_DATABASE: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(Some(c"postgres"));
+
+pub static MAINTENANCE_BATCH_SIZE: GucSetting<i32> = GucSetting::<i32>::new(1000);
+
+pub static REFRESH_THRESHOLD: GucSetting<f64> = GucSetting::<f64>::new(0.1);
+
+pub static DEFAULT_TOKENIZER: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(Some(c"hf"));
+
+pub static DEFAULT_MODEL: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(Some(c"google-bert/bert-base-uncased"));
+
+pub static DEFAULT_STYLE: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(Some(c"pgvecto.rs"));
+
+pub static DEFAULT_B: GucSetting<f64> = GucSetting::<f64>::new(0.75);
+
+pub static DEFAULT_K1: GucSetting<f64> = GucSetting::<f64>::new(1.2);
+
+pub static VOCAB_CACHE_SIZE: GucSetting<i32> = GucSetting::<i32>::new(16);
+
+pub static TOKENIZER_MODEL_DIR: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
+pub static JIEBA_DICT_DIR: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
+pub fn init() {
+    GucRegistry::define_int_guc(
+        "pg_bestmatch.maintenance_naptime",
+        "Time between rounds of the maintenance worker.",
+        "Time between rounds of the maintenance worker.",
+        &MAINTENANCE_NAPTIME,
+        1,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::UNIT_S,
+    );
+    GucRegistry::define_string_guc(
+        "pg_bestmatch.maintenance_database",
+        "Database maintained by the maintenance worker.",
+        "Database maintained by the maintenance worker.",
+        &MAINTENANCE_DATABASE,
+        GucContext::Postmaster,
+        GucFlags::default(),
+    );
+    GucRegistry::define_int_guc(
+        "pg_bestmatch.maintenance_batch_size",
+        "Rows of each attached column re-embedded by a round of the maintenance worker.",
+        "Rows of each attached column re-embedded by a round of the maintenance worker.",
+        &MAINTENANCE_BATCH_SIZE,
+        1,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+    GucRegistry::define_float_guc(
+        "pg_bestmatch.refresh_threshold",
+        "Fraction of changed rows that makes the maintenance worker refresh statistics.",
+        "Changes of the table since the last refresh, divided by the number of documents. 0 disables refreshing.",
+        &REFRESH_THRESHOLD,
+        0.0,
+        f64::MAX,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "pg_bestmatch.default_tokenizer",
+        "Tokenizer of new statistics.",
+        "Tokenizer of new statistics.",
+        &DEFAULT_TOKENIZER,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "pg_bestmatch.default_model",
+        "Model of the tokenizer of new statistics.",
+        "Model of the tokenizer of new statistics, with its options.",
+        &DEFAULT_MODEL,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "pg_bestmatch.default_style",
+        "Style of vectors.",
+        "Style of vectors, e.g. pgvecto.rs or pgvector.",
+        &DEFAULT_STYLE,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_float_guc(
+        "pg_bestmatch.default_b",
+        "BM25 parameter b of new statistics.",
+        "BM25 parameter b of new statistics.",
+        &DEFAULT_B,
+        0.0,
+        1.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_float_guc(
+        "pg_bestmatch.default_k1",
+        "BM25 parameter k1 of new statistics.",
+        "BM25 parameter k1 of new statistics.",
+        &DEFAULT_K1,
+        0.0,
+        f64::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_int_guc(
+        "pg_bestmatch.vocab_cache_size",
+        "Tokenizers kept in memory for each kind of tokenizer.",
+        "Loaded tokenizers with their vocabularies, kept for each kind of tokenizer in each session.",
+        &VOCAB_CACHE_SIZE,
+        1,
+        i32::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "pg_bestmatch.tokenizer_model_dir",
+        "Directory of local models of the hf tokenizer.",
+        "The hf tokenizer loads model m from m/tokenizer.json in this directory if it exists, and from the Hugging Face Hub otherwise.",
+        &TOKENIZER_MODEL_DIR,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "pg_bestmatch.jieba_dict_dir",
+        "Directory of jieba dictionary files.",
+        "The dict option of the jieba tokenizer names a file in this directory. Without it, only dict_table is available.",
+        &JIEBA_DICT_DIR,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+}
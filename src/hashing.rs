@@ -0,0 +1,55 @@
+/// MurmurHash3 (x86, 32-bit), the hash used by the hashing trick in scikit-learn and Spark.
+pub fn murmur3_32(key: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e2d51;
+    const C2: u32 = 0x1b873593;
+    let mut h = seed;
+    let mut chunks = key.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h ^= k;
+        h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let mut k = 0u32;
+        for (i, byte) in tail.iter().enumerate() {
+            k |= (*byte as u32) << (8 * i);
+        }
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h ^= k;
+    }
+    h ^= key.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85ebca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2ae35);
+    h ^= h >> 16;
+    h
+}
+
+/// Maps a token to one of `2^bits` buckets.
+pub fn bucket(token: &str, bits: u32, seed: u32) -> u32 {
+    murmur3_32(token.as_bytes(), seed) & ((1u32 << bits) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn test_murmur3_32() {
+        assert_eq!(super::murmur3_32(b"", 0), 0);
+        assert_eq!(super::murmur3_32(b"", 1), 0x514e28b7);
+        assert_eq!(super::murmur3_32(b"hello", 0), 0x248bfa47);
+        assert_eq!(
+            super::murmur3_32(b"The quick brown fox jumps over the lazy dog", 0),
+            0x2e4ff723
+        );
+    }
+
+    #[test]
+    fn test_bucket() {
+        assert_eq!(super::bucket("hello", 8, 0), 0x47);
+        assert!(super::bucket("apple", 4, 42) < 16);
+    }
+}
@@ -1,4 +1,15 @@
+mod builder;
+mod fusion;
+mod guc;
+mod hashing;
+mod metrics;
+mod phrase;
+mod query;
+mod scoring;
 mod tokenizer;
+mod worker;
+
+use std::collections::{BTreeMap, HashMap};
 
 pgrx::pg_module_magic!();
 pgrx::extension_sql_file!("./sql/finalize.sql", finalize);
@@ -18,214 +29,1043 @@ compile_error!("PostgreSQL version must be selected.");
 
 #[allow(non_snake_case)]
 #[pgrx::pg_guard]
-unsafe extern "C" fn _PG_init() {}
+unsafe extern "C" fn _PG_init() {
+    guc::init();
+    worker::init();
+}
 
-#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+#[pgrx::pg_extern(stable, strict, parallel_safe)]
 pub fn tokenize(t: &str, tokenizer: &str, model: Option<&str>) -> Vec<String> {
     tokenizer::tokenize(tokenizer, model, t)
 }
 
+#[pgrx::pg_extern(stable, strict, parallel_safe)]
+pub fn tokenize_positions(
+    t: &str,
+    tokenizer: &str,
+    model: Option<&str>,
+) -> pgrx::iter::TableIterator<'static, (pgrx::name!(token, String), pgrx::name!(position, i32))> {
+    let tokens = tokenizer::tokenize_unigrams(tokenizer, model, t);
+    pgrx::iter::TableIterator::new(
+        tokens
+            .into_iter()
+            .enumerate()
+            .map(|(position, token)| (token, position as i32)),
+    )
+}
+
+#[pgrx::pg_extern(stable, strict, parallel_safe)]
+pub fn phrase_score(
+    document: &str,
+    query: &str,
+    tokenizer: &str,
+    model: Option<&str>,
+    window_size: i32,
+) -> f32 {
+    if window_size < 1 {
+        pgrx::error!("window size must be positive, got {}", window_size);
+    }
+    let document = tokenizer::tokenize_unigrams(tokenizer, model, document);
+    let query = tokenizer::tokenize_unigrams(tokenizer, model, query);
+    phrase::matches(&document, &query, window_size as u32).score()
+}
+
+#[pgrx::pg_extern(stable, strict, parallel_safe)]
+pub fn tokenize_ids(t: &str, tokenizer: &str, model: Option<&str>) -> Vec<i32> {
+    if tokenizer::has_bigrams(model) {
+        pgrx::error!("bigrams have no native token ids");
+    }
+    tokenizer::tokenize_ids(tokenizer, model, t)
+        .into_iter()
+        .map(|id| id as i32)
+        .collect()
+}
+
+/// Unsigned MurmurHash3 of the UTF-8 bytes of `t`, as in `mmh3.hash(t, seed, signed=False)`.
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn murmur3_32(t: &str, seed: pgrx::default!(i32, 0)) -> i64 {
+    hashing::murmur3_32(t.as_bytes(), seed as u32) as i64
+}
+
+#[pgrx::pg_extern(stable, strict, parallel_safe)]
+pub fn tokenize_hashed(
+    t: &str,
+    tokenizer: &str,
+    model: Option<&str>,
+    bits: i32,
+    seed: i32,
+) -> Vec<i32> {
+    if !(1..=30).contains(&bits) {
+        pgrx::error!("hash bits must be between 1 and 30, got {}", bits);
+    }
+    tokenize(t, tokenizer, model)
+        .iter()
+        .map(|token| hashing::bucket(token, bits as u32, seed as u32) as i32)
+        .collect()
+}
+
+#[pgrx::pg_extern(immutable, parallel_safe)]
+pub fn has_bigrams(model: Option<&str>) -> bool {
+    tokenizer::has_bigrams(model)
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn vocab_size(tokenizer: &str, model: Option<&str>) -> i32 {
+    tokenizer::vocab_size(tokenizer, model) as i32
+}
+
+/// Judgements as relevance by document. `grades` defaults to 1 for every relevant document.
+fn judgements<'a>(
+    relevant: &'a [Option<String>],
+    grades: Option<&[Option<i32>]>,
+) -> HashMap<&'a str, i32> {
+    if let Some(grades) = grades {
+        if grades.len() != relevant.len() {
+            pgrx::error!(
+                "got {} grades for {} relevant documents",
+                grades.len(),
+                relevant.len()
+            );
+        }
+    }
+    relevant
+        .iter()
+        .enumerate()
+        .filter_map(|(i, document)| {
+            let grade = grades.map_or(Some(1), |grades| grades[i]);
+            Some((document.as_deref()?, grade?))
+        })
+        .collect()
+}
+
+fn ranking(ranked: &[Option<String>]) -> Vec<&str> {
+    ranked.iter().flatten().map(String::as_str).collect()
+}
+
+fn cutoff(k: i32) -> usize {
+    if k < 1 {
+        pgrx::error!("k must be positive, got {}", k);
+    }
+    k as usize
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn recall_at_k(ranked: Vec<Option<String>>, relevant: Vec<Option<String>>, k: i32) -> f32 {
+    metrics::recall_at_k(&ranking(&ranked), &judgements(&relevant, None), cutoff(k))
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn precision_at_k(ranked: Vec<Option<String>>, relevant: Vec<Option<String>>, k: i32) -> f32 {
+    metrics::precision_at_k(&ranking(&ranked), &judgements(&relevant, None), cutoff(k))
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn reciprocal_rank(ranked: Vec<Option<String>>, relevant: Vec<Option<String>>) -> f32 {
+    metrics::reciprocal_rank(&ranking(&ranked), &judgements(&relevant, None))
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn average_precision(ranked: Vec<Option<String>>, relevant: Vec<Option<String>>) -> f32 {
+    metrics::average_precision(&ranking(&ranked), &judgements(&relevant, None))
+}
+
+#[pgrx::pg_extern(immutable, parallel_safe)]
+pub fn ndcg_at_k(
+    ranked: Option<Vec<Option<String>>>,
+    relevant: Option<Vec<Option<String>>>,
+    k: Option<i32>,
+    grades: pgrx::default!(Option<Vec<Option<i32>>>, "NULL"),
+) -> Option<f32> {
+    let (ranked, relevant) = (ranked?, relevant?);
+    let relevance = judgements(&relevant, grades.as_deref());
+    Some(metrics::ndcg_at_k(
+        &ranking(&ranked),
+        &relevance,
+        cutoff(k?),
+    ))
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn fuse_rrf(
+    ids_a: Vec<Option<String>>,
+    ids_b: Vec<Option<String>>,
+    k: pgrx::default!(f32, 60.0),
+    weight_a: pgrx::default!(f32, 1.0),
+    weight_b: pgrx::default!(f32, 1.0),
+) -> pgrx::iter::TableIterator<'static, (pgrx::name!(id, String), pgrx::name!(score, f32))> {
+    let (a, b) = (ranking(&ids_a), ranking(&ids_b));
+    pgrx::iter::TableIterator::new(fusion::rrf(&[(&a, weight_a), (&b, weight_b)], k))
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn fuse_scores(
+    ids_a: Vec<Option<String>>,
+    scores_a: Vec<Option<f32>>,
+    ids_b: Vec<Option<String>>,
+    scores_b: Vec<Option<f32>>,
+    weight: pgrx::default!(f32, 0.5),
+    normalization: pgrx::default!(&str, "'minmax'"),
+) -> pgrx::iter::TableIterator<'static, (pgrx::name!(id, String), pgrx::name!(score, f32))> {
+    if ids_a.len() != scores_a.len() || ids_b.len() != scores_b.len() {
+        pgrx::error!("every id must have a score");
+    }
+    let normalization =
+        fusion::Normalization::parse(normalization).unwrap_or_else(|e| pgrx::error!("{}", e));
+    let (a, scores_a) = fusion::present(&ids_a, &scores_a);
+    let (b, scores_b) = fusion::present(&ids_b, &scores_b);
+    pgrx::iter::TableIterator::new(fusion::weighted(
+        &[(&a, &scores_a, weight), (&b, &scores_b, 1.0 - weight)],
+        normalization,
+    ))
+}
+
 #[derive(Debug)]
 #[repr(C)]
 struct RecordMat {
     token: [u8; pgrx::pg_sys::NAMEDATALEN as usize],
     id: i32,
     how_many_tokens: i32,
-    idf: f32,
+    token_in_how_many_inputs: i32,
 }
 
-#[allow(clippy::too_many_arguments)]
-#[pgrx::pg_extern(strict, parallel_safe)]
-pub fn bm25_document_to_svector_internal(
-    mat: pgrx::pg_sys::Oid,
-    idx: pgrx::pg_sys::Oid,
-    b: f32,
-    k1: f32,
-    words: i32,
-    docs: i32,
-    dims: i32,
-    t: &str,
-    style: &str,
-    tokenizer: &str,
-    model: Option<&str>,
-) -> String {
-    use std::collections::BTreeMap;
-    let tokens = tokenize(t, tokenizer, model);
-    let mut x = BTreeMap::<u32, u32>::new();
+#[derive(Debug)]
+#[repr(C)]
+struct RecordFixed {
+    id: i32,
+    how_many_tokens: i32,
+    token_in_how_many_inputs: i32,
+}
+
+/// A key of the statistic: the token in `corpus` statistics, the native id in `fixed` ones and
+/// the bucket in `hashed` ones.
+enum Term {
+    Token(std::ffi::CString),
+    Id(i32),
+}
+
+struct Entry {
+    id: u32,
+    df: i32,
+    cf: i32,
+}
+
+/// How a statistic turns text into terms.
+struct Vocabulary<'a> {
+    kind: &'a str,
+    hash_bits: i32,
+    hash_seed: i32,
+    tokenizer: &'a str,
+    model: Option<&'a str>,
+}
+
+impl Vocabulary<'_> {
+    fn terms(&self, t: &str) -> Vec<Term> {
+        let (tokenizer, model) = (self.tokenizer, self.model);
+        match self.kind {
+            "corpus" => tokenize(t, tokenizer, model)
+                .into_iter()
+                .filter_map(|token| std::ffi::CString::new(token).ok())
+                .map(Term::Token)
+                .collect(),
+            "fixed" => tokenize_ids(t, tokenizer, model)
+                .into_iter()
+                .map(Term::Id)
+                .collect(),
+            "hashed" => tokenize_hashed(t, tokenizer, model, self.hash_bits, self.hash_seed)
+                .into_iter()
+                .map(Term::Id)
+                .collect(),
+            _ => pgrx::error!("unknown vocabulary: {}", self.kind),
+        }
+    }
+
+    /// Terms together with the tokens they come from.
+    fn labeled_terms(&self, t: &str) -> Vec<(String, Term)> {
+        let tokens = tokenize(t, self.tokenizer, self.model);
+        match self.kind {
+            "corpus" => tokens
+                .into_iter()
+                .filter_map(|token| {
+                    let term = std::ffi::CString::new(token.as_str()).ok()?;
+                    Some((token, Term::Token(term)))
+                })
+                .collect(),
+            _ => tokens.into_iter().zip(self.terms(t)).collect(),
+        }
+    }
+}
+
+/// Looks up each term in the statistic. Terms that are not in the statistic yield `None`.
+fn lookup(mat: pgrx::pg_sys::Oid, idx: pgrx::pg_sys::Oid, terms: &[Term]) -> Vec<Option<Entry>> {
+    let mut result = Vec::with_capacity(terms.len());
     unsafe {
         use pgrx::pg_sys::*;
-        use std::collections::btree_map::Entry;
-        use std::ffi::CString;
         let heap = table_open(mat, AccessShareLock as _);
         let index = index_open(idx, AccessShareLock as _);
+        // a restored catalog refers to indexes of the dumped database until `bm25_restore`
+        if (*(*index).rd_index).indrelid != mat {
+            pgrx::error!(
+                "The index of BM25 statistics is stale. Run `bm25_restore` after restoring a dump."
+            );
+        }
         let slot = MakeSingleTupleTableSlot((*heap).rd_att, table_slot_callbacks(heap));
         let scan = index_beginscan(heap, index, GetActiveSnapshot(), 1, 0);
-        for token in tokens.iter() {
-            if let Ok(token) = CString::new(token.as_str()) {
-                let mut key = std::mem::zeroed::<ScanKeyData>();
-                pgrx::pg_sys::ScanKeyInit(
+        for term in terms.iter() {
+            let mut key = std::mem::zeroed::<ScanKeyData>();
+            match term {
+                Term::Token(token) => pgrx::pg_sys::ScanKeyInit(
                     &mut key,
                     /* attr 1 */ 1,
                     pgrx::pg_sys::BTEqualStrategyNumber as _,
                     pgrx::pg_sys::F_NAMEEQ.into(),
                     token.as_ptr().into(),
-                );
-                index_rescan(scan, &mut key, 1, std::ptr::null_mut(), 0);
-                if index_getnext_slot(scan, ScanDirection::ForwardScanDirection, slot) {
-                    let mut should_free = false;
-                    let tuple = ExecFetchSlotHeapTuple(slot, false, &mut should_free);
-                    debug_assert!(!tuple.is_null());
-                    let row = (*tuple)
-                        .t_data
-                        .cast::<u8>()
-                        .add((*(*tuple).t_data).t_hoff as _)
-                        .cast::<RecordMat>();
-                    let id = (*row).id as u32;
-                    match x.entry(id) {
-                        Entry::Vacant(e) => {
-                            e.insert(1);
-                        }
-                        Entry::Occupied(mut e) => {
-                            *e.get_mut() += 1;
+                ),
+                Term::Id(id) => pgrx::pg_sys::ScanKeyInit(
+                    &mut key,
+                    /* attr 1 */ 1,
+                    pgrx::pg_sys::BTEqualStrategyNumber as _,
+                    pgrx::pg_sys::F_INT4EQ.into(),
+                    (*id).into(),
+                ),
+            }
+            index_rescan(scan, &mut key, 1, std::ptr::null_mut(), 0);
+            if index_getnext_slot(scan, ScanDirection::ForwardScanDirection, slot) {
+                let mut should_free = false;
+                let tuple = ExecFetchSlotHeapTuple(slot, false, &mut should_free);
+                debug_assert!(!tuple.is_null());
+                let data = (*tuple)
+                    .t_data
+                    .cast::<u8>()
+                    .add((*(*tuple).t_data).t_hoff as _);
+                let entry = match term {
+                    Term::Token(_) => {
+                        let row = data.cast::<RecordMat>();
+                        Entry {
+                            id: (*row).id as u32,
+                            df: (*row).token_in_how_many_inputs,
+                            cf: (*row).how_many_tokens,
                         }
                     }
-                    if should_free {
-                        pfree(tuple.cast());
+                    Term::Id(_) => {
+                        let row = data.cast::<RecordFixed>();
+                        Entry {
+                            id: (*row).id as u32,
+                            df: (*row).token_in_how_many_inputs,
+                            cf: (*row).how_many_tokens,
+                        }
                     }
+                };
+                result.push(Some(entry));
+                if should_free {
+                    pfree(tuple.cast());
                 }
+            } else {
+                result.push(None);
             }
         }
         index_endscan(scan);
         ExecDropSingleTupleTableSlot(slot);
-        index_close(index, AccessShareLock as _);
-        table_close(heap, AccessShareLock as _);
+        // the locks are held until the end of the transaction, so that `bm25_gc` in another
+        // session waits for readers of the generation instead of dropping it under them
+        index_close(index, NoLock as _);
+        table_close(heap, NoLock as _);
     }
-    match style {
-        "pgvecto.rs" => {
-            let avgdl = words as f32 / docs as f32;
-            let length = x.values().sum::<u32>() as f32;
-            let mut result = "{".to_string();
-            for (index, value) in x.into_iter() {
-                let value = value as f32 / (value as f32 + k1 * ((1.0 - b) + b * (length / avgdl)));
-                result.push_str(&format!("{}:{value}, ", index));
+    result
+}
+
+enum Normalize {
+    L2,
+    Max,
+}
+
+/// Output format of a vector: `pgvecto.rs` or `pgvector`, optionally followed by options, e.g.
+/// `pgvector,normalize=l2`.
+struct Style {
+    offset: u32,
+    normalize: Option<Normalize>,
+}
+
+impl Style {
+    fn parse(style: &str) -> Style {
+        let mut items = style.split(',').map(str::trim);
+        let offset = match items.next().unwrap_or_default() {
+            "pgvecto.rs" => 0,
+            "pgvector" => 1,
+            _ => pgrx::error!("unknown svector style: {}", style),
+        };
+        let mut result = Style {
+            offset,
+            normalize: None,
+        };
+        for item in items {
+            match item.split_once('=') {
+                Some(("normalize", "l2")) => result.normalize = Some(Normalize::L2),
+                Some(("normalize", "max")) => result.normalize = Some(Normalize::Max),
+                _ => pgrx::error!("unknown svector style option: {}", item),
             }
-            if result.ends_with(", ") {
-                result.pop();
-                result.pop();
+        }
+        result
+    }
+}
+
+fn format_svector(x: impl IntoIterator<Item = (u32, f32)>, dims: i32, style: &Style) -> String {
+    let mut result = "{".to_string();
+    for (index, value) in x {
+        result.push_str(&format!("{}:{value}, ", index + style.offset));
+    }
+    if result.ends_with(", ") {
+        result.pop();
+        result.pop();
+    }
+    result.push('}');
+    result.push('/');
+    result.push_str(&dims.to_string());
+    result
+}
+
+/// Term frequencies of a document by dimension.
+fn document_tf(
+    mat: pgrx::pg_sys::Oid,
+    idx: pgrx::pg_sys::Oid,
+    vocabulary: &Vocabulary,
+    t: &str,
+) -> BTreeMap<u32, u32> {
+    let terms = vocabulary.terms(t);
+    let mut x = BTreeMap::<u32, u32>::new();
+    match vocabulary.kind {
+        // native ids or buckets are the dimensions, so there is nothing to look up
+        "fixed" | "hashed" => {
+            for term in terms.iter() {
+                if let Term::Id(id) = term {
+                    *x.entry(*id as u32).or_default() += 1;
+                }
             }
-            result.push('}');
-            result.push('/');
-            result.push_str(&dims.to_string());
-            result
         }
-        "pgvector" => {
-            let avgdl = words as f32 / docs as f32;
-            let length = x.values().sum::<u32>() as f32;
-            let mut result = "{".to_string();
-            for (index, value) in x.into_iter() {
-                let value = value as f32 / (value as f32 + k1 * ((1.0 - b) + b * (length / avgdl)));
-                result.push_str(&format!("{}:{value}, ", index + 1));
+        _ => {
+            for entry in lookup(mat, idx, &terms).into_iter().flatten() {
+                *x.entry(entry.id).or_default() += 1;
+            }
+        }
+    }
+    x
+}
+
+/// Idf of each dimension of a query, multiplied by the boost of the text it comes from.
+fn query_idf(
+    mat: pgrx::pg_sys::Oid,
+    idx: pgrx::pg_sys::Oid,
+    vocabulary: &Vocabulary,
+    docs: i32,
+    idf: scoring::Idf,
+    texts: &[(&str, f32)],
+) -> BTreeMap<u32, f32> {
+    let mut x = BTreeMap::<u32, f32>::new();
+    for (t, boost) in texts.iter() {
+        let terms = vocabulary.terms(t);
+        for entry in lookup(mat, idx, &terms).into_iter().flatten() {
+            let value = x.entry(entry.id).or_default();
+            *value = value.max(idf.idf(docs, entry.df) * boost);
+        }
+    }
+    x
+}
+
+/// Normalizes query weights as the style asks. `normalize=max` divides them by the largest score a
+/// document of the statistic could get, so that scores fall in `[0, 1]`.
+#[allow(clippy::too_many_arguments)]
+fn normalize_query(
+    mat: pgrx::pg_sys::Oid,
+    idx: pgrx::pg_sys::Oid,
+    vocabulary: &Vocabulary,
+    b: f32,
+    k1: f32,
+    words: i32,
+    docs: i32,
+    texts: &[(&str, f32)],
+    weights: BTreeMap<u32, f32>,
+    style: &Style,
+) -> BTreeMap<u32, f32> {
+    match style.normalize {
+        None => weights,
+        Some(Normalize::L2) => scoring::l2_normalize(weights),
+        Some(Normalize::Max) => {
+            let mut cf = BTreeMap::<u32, i32>::new();
+            for (t, _) in texts.iter() {
+                for entry in lookup(mat, idx, &vocabulary.terms(t)).into_iter().flatten() {
+                    cf.insert(entry.id, entry.cf);
+                }
             }
-            if result.ends_with(", ") {
-                result.pop();
-                result.pop();
+            let bound = weights
+                .iter()
+                .map(|(id, w)| w * scoring::max_weight(cf[id], b, k1, words, docs))
+                .sum::<f32>();
+            if bound == 0.0 {
+                return weights;
             }
-            result.push('}');
-            result.push('/');
-            result.push_str(&dims.to_string());
-            result
+            weights.into_iter().map(|(id, w)| (id, w / bound)).collect()
         }
-        _ => pgrx::error!("unknown svector style: {}", style),
     }
 }
 
+/// The value of setting `pg_bestmatch.default_<name>`, used as the default of function arguments.
+#[pgrx::pg_extern(stable, strict, parallel_safe)]
+pub fn bm25_default(name: &str) -> String {
+    let text = |setting: &pgrx::guc::GucSetting<Option<&'static std::ffi::CStr>>| {
+        setting
+            .get()
+            .map(|value| value.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    match name {
+        "tokenizer" => text(&guc::DEFAULT_TOKENIZER),
+        "model" => text(&guc::DEFAULT_MODEL),
+        "style" => text(&guc::DEFAULT_STYLE),
+        "b" => guc::DEFAULT_B.get().to_string(),
+        "k1" => guc::DEFAULT_K1.get().to_string(),
+        _ => pgrx::error!("unknown setting: {}", name),
+    }
+}
+
+fn parse_idf(idf: &str) -> scoring::Idf {
+    scoring::Idf::parse(idf).unwrap_or_else(|e| pgrx::error!("{}", e))
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn bm25_idf(idf: &str, docs: i32, df: i32) -> f32 {
+    parse_idf(idf).idf(docs, df)
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn bm25_sigmoid(score: f32, midpoint: f32, slope: f32) -> f32 {
+    scoring::sigmoid(score, midpoint, slope)
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn bm25_fit_sigmoid(
+    scores: Vec<f32>,
+    relevant: Vec<bool>,
+) -> pgrx::iter::TableIterator<'static, (pgrx::name!(midpoint, f32), pgrx::name!(slope, f32))> {
+    if scores.len() != relevant.len() {
+        pgrx::error!("got {} labels for {} scores", relevant.len(), scores.len());
+    }
+    let Some(fit) = scoring::fit_sigmoid(&scores, &relevant) else {
+        pgrx::error!(
+            "cannot fit a sigmoid: it needs both labels, with higher scores for relevant documents"
+        );
+    };
+    pgrx::iter::TableIterator::once(fit)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pgrx::pg_extern(strict, parallel_safe)]
+pub fn bm25_document_to_svector_internal(
+    mat: pgrx::pg_sys::Oid,
+    idx: pgrx::pg_sys::Oid,
+    b: f32,
+    k1: f32,
+    words: i32,
+    docs: i32,
+    dims: i32,
+    t: &str,
+    style: &str,
+    vocabulary: &str,
+    hash_bits: i32,
+    hash_seed: i32,
+    tokenizer: &str,
+    model: Option<&str>,
+) -> String {
+    let vocabulary = Vocabulary {
+        kind: vocabulary,
+        hash_bits,
+        hash_seed,
+        tokenizer,
+        model,
+    };
+    let style = Style::parse(style);
+    let x = document_tf(mat, idx, &vocabulary, t);
+    let (_, weights) = scoring::document_weights(&x, b, k1, words, docs);
+    let weights = match style.normalize {
+        None => weights,
+        Some(Normalize::L2) => scoring::l2_normalize(weights),
+        Some(Normalize::Max) => pgrx::error!("normalize=max only applies to query vectors"),
+    };
+    format_svector(weights, dims, &style)
+}
+
+#[allow(clippy::too_many_arguments)]
 #[pgrx::pg_extern(strict, parallel_safe)]
 pub fn bm25_query_to_svector_internal(
     mat: pgrx::pg_sys::Oid,
     idx: pgrx::pg_sys::Oid,
+    b: f32,
+    k1: f32,
+    words: i32,
+    docs: i32,
     dims: i32,
+    idf: &str,
     t: &str,
     style: &str,
+    vocabulary: &str,
+    hash_bits: i32,
+    hash_seed: i32,
     tokenizer: &str,
     model: Option<&str>,
 ) -> String {
-    use std::collections::BTreeMap;
-    let tokens = tokenize(t, tokenizer, model);
-    let mut x = BTreeMap::<u32, f32>::new();
-    unsafe {
-        use pgrx::pg_sys::*;
-        use std::ffi::CString;
-        let heap = table_open(mat, AccessShareLock as _);
-        let index = index_open(idx, AccessShareLock as _);
-        let slot = MakeSingleTupleTableSlot((*heap).rd_att, table_slot_callbacks(heap));
-        let scan = index_beginscan(heap, index, GetActiveSnapshot(), 1, 0);
-        for token in tokens.iter() {
-            if let Ok(token) = CString::new(token.as_str()) {
-                let mut key = std::mem::zeroed::<ScanKeyData>();
-                pgrx::pg_sys::ScanKeyInit(
-                    &mut key,
-                    /* attr 1 */ 1,
-                    pgrx::pg_sys::BTEqualStrategyNumber as _,
-                    pgrx::pg_sys::F_NAMEEQ.into(),
-                    token.as_ptr().into(),
-                );
-                index_rescan(scan, &mut key, 1, std::ptr::null_mut(), 0);
-                if index_getnext_slot(scan, ScanDirection::ForwardScanDirection, slot) {
-                    let mut should_free = false;
-                    let tuple = ExecFetchSlotHeapTuple(slot, false, &mut should_free);
-                    debug_assert!(!tuple.is_null());
-                    let row = (*tuple)
-                        .t_data
-                        .cast::<u8>()
-                        .add((*(*tuple).t_data).t_hoff as _)
-                        .cast::<RecordMat>();
-                    let id = (*row).id as u32;
-                    let idf = (*row).idf;
-                    x.insert(id, idf);
-                    if should_free {
-                        pfree(tuple.cast());
-                    }
+    let vocabulary = Vocabulary {
+        kind: vocabulary,
+        hash_bits,
+        hash_seed,
+        tokenizer,
+        model,
+    };
+    let style = Style::parse(style);
+    let texts = [(t, 1.0)];
+    let x = query_idf(mat, idx, &vocabulary, docs, parse_idf(idf), &texts);
+    let weights = scoring::query_weights(x);
+    let weights = normalize_query(
+        mat,
+        idx,
+        &vocabulary,
+        b,
+        k1,
+        words,
+        docs,
+        &texts,
+        weights,
+        &style,
+    );
+    format_svector(weights, dims, &style)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pgrx::pg_extern(strict, parallel_safe)]
+pub fn bm25_structured_query_to_svector_internal(
+    mat: pgrx::pg_sys::Oid,
+    idx: pgrx::pg_sys::Oid,
+    b: f32,
+    k1: f32,
+    words: i32,
+    docs: i32,
+    dims: i32,
+    idf: &str,
+    t: &str,
+    style: &str,
+    vocabulary: &str,
+    hash_bits: i32,
+    hash_seed: i32,
+    tokenizer: &str,
+    model: Option<&str>,
+) -> String {
+    let vocabulary = Vocabulary {
+        kind: vocabulary,
+        hash_bits,
+        hash_seed,
+        tokenizer,
+        model,
+    };
+    let clauses = query::parse(t).unwrap_or_else(|e| pgrx::error!("invalid query: {}", e));
+    let texts = query::weighted_texts(&clauses);
+    let style = Style::parse(style);
+    let x = query_idf(mat, idx, &vocabulary, docs, parse_idf(idf), &texts);
+    let weights = scoring::query_weights(x);
+    let weights = normalize_query(
+        mat,
+        idx,
+        &vocabulary,
+        b,
+        k1,
+        words,
+        docs,
+        &texts,
+        weights,
+        &style,
+    );
+    format_svector(weights, dims, &style)
+}
+
+#[pgrx::pg_extern(stable, strict, parallel_safe)]
+pub fn structured_query_match(
+    document: &str,
+    query: &str,
+    tokenizer: &str,
+    model: Option<&str>,
+) -> bool {
+    let clauses = query::parse(query).unwrap_or_else(|e| pgrx::error!("invalid query: {}", e));
+    let document = tokenizer::tokenize_unigrams(tokenizer, model, document);
+    query::filter(&clauses, &mut |text| {
+        let text = tokenizer::tokenize_unigrams(tokenizer, model, text);
+        phrase::matches(&document, &text, 1).phrases > 0
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+#[pgrx::pg_extern(strict, parallel_safe)]
+pub fn bm25_explain_internal(
+    mat: pgrx::pg_sys::Oid,
+    idx: pgrx::pg_sys::Oid,
+    b: f32,
+    k1: f32,
+    words: i32,
+    docs: i32,
+    idf_variant: &str,
+    document: &str,
+    query: &str,
+    style: &str,
+    vocabulary: &str,
+    hash_bits: i32,
+    hash_seed: i32,
+    tokenizer: &str,
+    model: Option<&str>,
+) -> pgrx::iter::TableIterator<
+    'static,
+    (
+        pgrx::name!(token, String),
+        pgrx::name!(id, i32),
+        pgrx::name!(query_weight, f32),
+        pgrx::name!(tf, i32),
+        pgrx::name!(idf, f32),
+        pgrx::name!(norm, f32),
+        pgrx::name!(document_weight, f32),
+        pgrx::name!(contribution, f32),
+    ),
+> {
+    let vocabulary = Vocabulary {
+        kind: vocabulary,
+        hash_bits,
+        hash_seed,
+        tokenizer,
+        model,
+    };
+    let style = Style::parse(style);
+    let tf = document_tf(mat, idx, &vocabulary, document);
+    let (norm, document_weights) = scoring::document_weights(&tf, b, k1, words, docs);
+    // documents are embedded without `normalize=max`, which only applies to queries
+    let document_weights = match style.normalize {
+        Some(Normalize::L2) => scoring::l2_normalize(document_weights),
+        _ => document_weights,
+    };
+    let texts = [(query, 1.0)];
+    let idf = query_idf(mat, idx, &vocabulary, docs, parse_idf(idf_variant), &texts);
+    let query_weights = normalize_query(
+        mat,
+        idx,
+        &vocabulary,
+        b,
+        k1,
+        words,
+        docs,
+        &texts,
+        scoring::query_weights(idf.clone()),
+        &style,
+    );
+    let (labels, terms): (Vec<_>, Vec<_>) = vocabulary.labeled_terms(query).into_iter().unzip();
+    let mut tokens = BTreeMap::<u32, String>::new();
+    for (token, entry) in labels.into_iter().zip(lookup(mat, idx, &terms)) {
+        if let Some(entry) = entry {
+            tokens.entry(entry.id).or_insert(token);
+        }
+    }
+    let mut rows = query_weights
+        .iter()
+        .map(|(id, query_weight)| {
+            let document_weight = document_weights.get(id).copied().unwrap_or(0.0);
+            (
+                tokens.get(id).cloned().unwrap_or_default(),
+                *id as i32,
+                *query_weight,
+                tf.get(id).copied().unwrap_or(0) as i32,
+                idf[id],
+                norm,
+                document_weight,
+                query_weight * document_weight,
+            )
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by(|a, b| b.7.total_cmp(&a.7));
+    pgrx::iter::TableIterator::new(rows)
+}
+
+/// Ranks the documents of `documents` (rows of id and text) for each query of `queries` (rows of
+/// id and text) with every pair of `b_grid` and `k1_grid`, and reports recall@k, MRR@k and
+/// nDCG@k averaged over the queries judged in `qrels` (rows of query id, document id and
+/// relevance).
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+#[pgrx::pg_extern(strict)]
+pub fn bm25_tune_internal(
+    mat: pgrx::pg_sys::Oid,
+    idx: pgrx::pg_sys::Oid,
+    words: i32,
+    docs: i32,
+    idf: &str,
+    documents: pgrx::pg_sys::Oid,
+    doc_id_col: &str,
+    doc_col: &str,
+    queries: pgrx::pg_sys::Oid,
+    query_id_col: &str,
+    query_col: &str,
+    qrels: pgrx::pg_sys::Oid,
+    b_grid: Vec<f32>,
+    k1_grid: Vec<f32>,
+    k: i32,
+    vocabulary: &str,
+    hash_bits: i32,
+    hash_seed: i32,
+    tokenizer: &str,
+    model: Option<&str>,
+) -> pgrx::iter::TableIterator<
+    'static,
+    (
+        pgrx::name!(b, f32),
+        pgrx::name!(k1, f32),
+        pgrx::name!(recall, f32),
+        pgrx::name!(mrr, f32),
+        pgrx::name!(ndcg, f32),
+    ),
+> {
+    if k < 1 {
+        pgrx::error!("k must be positive, got {}", k);
+    }
+    if b_grid.iter().any(|b| !(0.0..=1.0).contains(b)) || k1_grid.iter().any(|k1| *k1 < 0.0) {
+        pgrx::error!("BM25 parameters must satisfy 0 <= b <= 1 and k1 >= 0");
+    }
+    let vocabulary = Vocabulary {
+        kind: vocabulary,
+        hash_bits,
+        hash_seed,
+        tokenizer,
+        model,
+    };
+    let idf = parse_idf(idf);
+    use pgrx::IntoDatum;
+    // judgements without a `relevance` column are binary
+    let (documents, queries, qrels) =
+        pgrx::spi::Spi::get_three_with_args::<String, String, String>(
+            "SELECT format('SELECT %I::text, %I FROM %s', $2, $3, $1::regclass),
+            format('SELECT %I::text, %I::text FROM %s', $5, $6, $4::regclass),
+            format('SELECT query_id::text, doc_id::text, %s FROM %s',
+                CASE WHEN EXISTS (SELECT 1 FROM pg_catalog.pg_attribute a
+                    WHERE a.attrelid = $7 AND a.attname = 'relevance' AND NOT a.attisdropped)
+                THEN 'relevance::int' ELSE '1' END, $7::regclass)",
+            vec![
+                (pgrx::PgBuiltInOids::OIDOID.oid(), documents.into_datum()),
+                (pgrx::PgBuiltInOids::TEXTOID.oid(), doc_id_col.into_datum()),
+                (pgrx::PgBuiltInOids::TEXTOID.oid(), doc_col.into_datum()),
+                (pgrx::PgBuiltInOids::OIDOID.oid(), queries.into_datum()),
+                (
+                    pgrx::PgBuiltInOids::TEXTOID.oid(),
+                    query_id_col.into_datum(),
+                ),
+                (pgrx::PgBuiltInOids::TEXTOID.oid(), query_col.into_datum()),
+                (pgrx::PgBuiltInOids::OIDOID.oid(), qrels.into_datum()),
+            ],
+        )
+        .unwrap();
+    let (documents, queries, qrels) = (documents.unwrap(), queries.unwrap(), qrels.unwrap());
+    let (index, document_ids, judged) = pgrx::spi::Spi::connect(|client| {
+        let mut relevance = HashMap::<String, HashMap<String, i32>>::new();
+        for row in client.select(&qrels, None, None).unwrap() {
+            let query_id = row.get::<String>(1).unwrap();
+            let document_id = row.get::<String>(2).unwrap();
+            let grade = row.get::<i32>(3).unwrap().unwrap_or(1);
+            if let (Some(query_id), Some(document_id)) = (query_id, document_id) {
+                relevance
+                    .entry(query_id)
+                    .or_default()
+                    .insert(document_id, grade);
+            }
+        }
+        let mut index = scoring::Index::default();
+        let mut document_ids = Vec::new();
+        for row in client.select(&documents, None, None).unwrap() {
+            let id = row.get::<String>(1).unwrap();
+            let t = row.get::<String>(2).unwrap();
+            if let (Some(id), Some(t)) = (id, t) {
+                index.push(&document_tf(mat, idx, &vocabulary, &t));
+                document_ids.push(id);
+            }
+        }
+        let mut judged = Vec::new();
+        for row in client.select(&queries, None, None).unwrap() {
+            let id = row.get::<String>(1).unwrap();
+            let t = row.get::<String>(2).unwrap();
+            if let (Some(relevance), Some(t)) = (id.and_then(|id| relevance.remove(&id)), t) {
+                let x = query_idf(mat, idx, &vocabulary, docs, idf, &[(t.as_str(), 1.0)]);
+                judged.push((scoring::query_weights(x), relevance));
+            }
+        }
+        (index, document_ids, judged)
+    });
+    if judged.is_empty() {
+        pgrx::error!("no query is judged");
+    }
+    let judged = judged
+        .iter()
+        .map(|(query, relevance)| {
+            let relevance = relevance
+                .iter()
+                .map(|(id, grade)| (id.as_str(), *grade))
+                .collect::<HashMap<_, _>>();
+            (query, relevance)
+        })
+        .collect::<Vec<_>>();
+    let mut rows = Vec::with_capacity(b_grid.len() * k1_grid.len());
+    for b in b_grid.iter().copied() {
+        for k1 in k1_grid.iter().copied() {
+            let (mut recall, mut mrr, mut ndcg) = (0.0, 0.0, 0.0);
+            for (query, relevance) in judged.iter() {
+                let ranked = index
+                    .top_k(query, b, k1, words, docs, k as usize)
+                    .into_iter()
+                    .map(|document| document_ids[document as usize].as_str())
+                    .collect::<Vec<_>>();
+                recall += metrics::recall_at_k(&ranked, relevance, k as usize);
+                mrr += metrics::reciprocal_rank(&ranked, relevance);
+                ndcg += metrics::ndcg_at_k(&ranked, relevance, k as usize);
+            }
+            let n = judged.len() as f32;
+            rows.push((b, k1, recall / n, mrr / n, ndcg / n));
+        }
+    }
+    pgrx::iter::TableIterator::new(rows)
+}
+
+/// Documents fetched for each thread at a time by `bm25_count_terms`.
+const BUILD_BATCH_SIZE: usize = 256;
+
+static BUILD_RUNS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Counts the terms of the documents of `query` in one scan. The leader and
+/// `max_parallel_maintenance_workers` threads tokenize the documents, and counts beyond
+/// `maintenance_work_mem` are spilled to temporary files.
+fn count_terms<K: builder::Key + Send + 'static>(
+    query: &str,
+    terms: impl Fn(&str) -> Vec<K> + Sync,
+) -> builder::Counts<K> {
+    let threads = unsafe { pgrx::pg_sys::max_parallel_maintenance_workers }.max(0) as usize + 1;
+    let limit = unsafe { pgrx::pg_sys::maintenance_work_mem }.max(0) as usize * 1024;
+    // the temporary directory of the default tablespace, cleaned up by a restart
+    let dir = std::path::PathBuf::from("base/pgsql_tmp");
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        pgrx::error!("could not create directory {}: {}", dir.display(), e);
+    }
+    let run = BUILD_RUNS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let prefix = format!("pgsql_tmp{}.bm25_{}", std::process::id(), run);
+    let mut counter = builder::Counter::new(limit, dir, prefix);
+    pgrx::spi::Spi::connect(|client| {
+        let mut cursor = client.open_cursor(query, None);
+        loop {
+            let table = cursor.fetch((threads * BUILD_BATCH_SIZE) as _).unwrap();
+            if table.is_empty() {
+                break;
+            }
+            let documents = table
+                .filter_map(|row| row.get::<String>(1).unwrap())
+                .collect::<Vec<_>>();
+            // threads must not call into Postgres, errors are raised by the leader
+            let chunk_size = documents.len().div_ceil(threads).max(1);
+            let chunks = std::thread::scope(|scope| {
+                let handles = documents
+                    .chunks(chunk_size)
+                    .map(|chunk| {
+                        let terms = &terms;
+                        scope.spawn(move || chunk.iter().map(|t| terms(t)).collect::<Vec<_>>())
+                    })
+                    .collect::<Vec<_>>();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle
+                            .join()
+                            .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                    })
+                    .collect::<Vec<_>>()
+            });
+            for terms in chunks.into_iter().flatten() {
+                if let Err(e) = counter.push(terms) {
+                    pgrx::error!("could not spill counts: {}", e);
                 }
             }
         }
-        index_endscan(scan);
-        ExecDropSingleTupleTableSlot(slot);
-        index_close(index, AccessShareLock as _);
-        table_close(heap, AccessShareLock as _);
-    }
-    match style {
-        "pgvecto.rs" => {
-            // https://github.com/pinecone-io/pinecone-text/issues/69
-            let sum = x.values().copied().sum::<f32>();
-            let mut result = "{".to_string();
-            for (index, value) in x.into_iter() {
-                result.push_str(&format!("{}:{}, ", index, value / sum));
-            }
-            if result.ends_with(", ") {
-                result.pop();
-                result.pop();
-            }
-            result.push('}');
-            result.push('/');
-            result.push_str(&dims.to_string());
-            result
-        }
-        "pgvector" => {
-            // https://github.com/pinecone-io/pinecone-text/issues/69
-            let sum = x.values().copied().sum::<f32>();
-            let mut result = "{".to_string();
-            for (index, value) in x.into_iter() {
-                result.push_str(&format!("{}:{}, ", index + 1, value / sum));
-            }
-            if result.ends_with(", ") {
-                result.pop();
-                result.pop();
-            }
-            result.push('}');
-            result.push('/');
-            result.push_str(&dims.to_string());
-            result
-        }
-        _ => pgrx::error!("unknown svector style: {}", style),
+    });
+    counter
+        .finish()
+        .unwrap_or_else(|e| pgrx::error!("could not merge spilled counts: {}", e))
+}
+
+/// Term counts of a column, for building a generation of a statistic. `token` is set in `corpus`
+/// vocabularies, and `id` in `fixed` and `hashed` ones.
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+#[pgrx::pg_extern(strict)]
+pub fn bm25_count_terms(
+    tab: pgrx::pg_sys::Oid,
+    col: &str,
+    tokenizer: &str,
+    model: Option<&str>,
+    vocabulary: &str,
+    hash_bits: i32,
+    hash_seed: i32,
+) -> pgrx::iter::TableIterator<
+    'static,
+    (
+        pgrx::name!(token, Option<String>),
+        pgrx::name!(id, Option<i32>),
+        pgrx::name!(how_many_tokens, i64),
+        pgrx::name!(token_in_how_many_inputs, i64),
+    ),
+> {
+    use pgrx::IntoDatum;
+    let query = pgrx::spi::Spi::get_one_with_args::<String>(
+        "SELECT format('SELECT %I::TEXT FROM %s', a.attname, a.attrelid::regclass)
+        FROM pg_catalog.pg_attribute a
+        WHERE a.attrelid = $1 AND a.attname = $2 AND a.attnum > 0 AND NOT a.attisdropped",
+        vec![
+            (pgrx::PgBuiltInOids::OIDOID.oid(), tab.into_datum()),
+            (pgrx::PgBuiltInOids::TEXTOID.oid(), col.into_datum()),
+        ],
+    )
+    .unwrap()
+    .unwrap_or_else(|| pgrx::error!("column {} of the table does not exist", col));
+    // load the tokenizer in the leader, as some tokenizers read their dictionary with SPI
+    let loaded = tokenizer::load(tokenizer, model);
+    match vocabulary {
+        "corpus" => pgrx::iter::TableIterator::new(
+            count_terms(&query, |t| loaded.tokenize(t))
+                .map(|(token, cf, df)| (Some(token), None, cf, df)),
+        ),
+        "fixed" => {
+            if tokenizer::has_bigrams(model) {
+                pgrx::error!("bigrams have no native token ids");
+            }
+            if loaded.tokenize_ids("").is_none() {
+                pgrx::error!("tokenizer {} has no native token ids", tokenizer);
+            }
+            pgrx::iter::TableIterator::new(
+                count_terms(&query, |t| {
+                    let ids = loaded.tokenize_ids(t).unwrap_or_default();
+                    ids.into_iter().map(|id| id as i32).collect()
+                })
+                .map(|(id, cf, df)| (None, Some(id), cf, df)),
+            )
+        }
+        "hashed" => {
+            if !(1..=30).contains(&hash_bits) {
+                pgrx::error!("hash bits must be between 1 and 30, got {}", hash_bits);
+            }
+            let (bits, seed) = (hash_bits as u32, hash_seed as u32);
+            pgrx::iter::TableIterator::new(
+                count_terms(&query, |t| {
+                    loaded
+                        .tokenize(t)
+                        .iter()
+                        .map(|token| hashing::bucket(token, bits, seed) as i32)
+                        .collect()
+                })
+                .map(|(id, cf, df)| (None, Some(id), cf, df)),
+            )
+        }
+        _ => pgrx::error!("unknown vocabulary: {}", vocabulary),
     }
 }
@@ -0,0 +1,144 @@
+//! Retrieval metrics of a ranking against graded relevance judgements. A document is relevant if
+//! its relevance is positive. As in `fusion`, a document listed twice in a ranking counts at its
+//! best rank, and its later entries are dropped from the ranking.
+
+use std::collections::{HashMap, HashSet};
+
+fn distinct<'a>(ranked: &[&'a str]) -> Vec<&'a str> {
+    let mut seen = HashSet::new();
+    ranked.iter().copied().filter(|d| seen.insert(*d)).collect()
+}
+
+fn relevant(relevance: &HashMap<&str, i32>) -> usize {
+    relevance.values().filter(|r| **r > 0).count()
+}
+
+fn grade(relevance: &HashMap<&str, i32>, document: &str) -> i32 {
+    relevance.get(document).copied().unwrap_or(0).max(0)
+}
+
+/// Fraction of the relevant documents found in the first `k` ranks.
+pub fn recall_at_k(ranked: &[&str], relevance: &HashMap<&str, i32>, k: usize) -> f32 {
+    let total = relevant(relevance);
+    if total == 0 {
+        return 0.0;
+    }
+    let found = distinct(ranked)
+        .into_iter()
+        .take(k)
+        .filter(|d| grade(relevance, d) > 0)
+        .count();
+    found as f32 / total as f32
+}
+
+/// Fraction of the first `k` ranks that are relevant. Missing ranks count as irrelevant.
+pub fn precision_at_k(ranked: &[&str], relevance: &HashMap<&str, i32>, k: usize) -> f32 {
+    if k == 0 {
+        return 0.0;
+    }
+    let found = distinct(ranked)
+        .into_iter()
+        .take(k)
+        .filter(|d| grade(relevance, d) > 0)
+        .count();
+    found as f32 / k as f32
+}
+
+/// Reciprocal of the rank of the first relevant document, or 0 if there is none.
+pub fn reciprocal_rank(ranked: &[&str], relevance: &HashMap<&str, i32>) -> f32 {
+    distinct(ranked)
+        .into_iter()
+        .position(|d| grade(relevance, d) > 0)
+        .map_or(0.0, |position| 1.0 / (position + 1) as f32)
+}
+
+/// Mean of the precision at the rank of each relevant document, where relevant documents missing
+/// from the ranking count as 0.
+pub fn average_precision(ranked: &[&str], relevance: &HashMap<&str, i32>) -> f32 {
+    let total = relevant(relevance);
+    if total == 0 {
+        return 0.0;
+    }
+    let mut found = 0;
+    let mut sum = 0.0;
+    for (position, document) in distinct(ranked).into_iter().enumerate() {
+        if grade(relevance, document) > 0 {
+            found += 1;
+            sum += found as f32 / (position + 1) as f32;
+        }
+    }
+    sum / total as f32
+}
+
+fn dcg(grades: impl Iterator<Item = i32>) -> f64 {
+    grades
+        .enumerate()
+        .map(|(position, grade)| {
+            ((1u64 << grade.min(62)) - 1) as f64 / (position as f64 + 2.0).log2()
+        })
+        .sum()
+}
+
+/// Discounted cumulative gain of the first `k` ranks with gain `2^relevance - 1`, divided by the
+/// gain of the ideal ranking.
+pub fn ndcg_at_k(ranked: &[&str], relevance: &HashMap<&str, i32>, k: usize) -> f32 {
+    let mut ideal = relevance.values().map(|r| (*r).max(0)).collect::<Vec<_>>();
+    ideal.sort_unstable_by(|a, b| b.cmp(a));
+    let ideal = dcg(ideal.into_iter().take(k));
+    if ideal == 0.0 {
+        return 0.0;
+    }
+    let actual = dcg(distinct(ranked)
+        .into_iter()
+        .take(k)
+        .map(|d| grade(relevance, d)));
+    (actual / ideal) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_metrics() {
+        let ranked = ["a", "b", "c", "d"];
+        let relevance = HashMap::from([("b", 1), ("d", 2), ("e", 1), ("f", 0)]);
+        assert_eq!(super::recall_at_k(&ranked, &relevance, 2), 1.0 / 3.0);
+        assert_eq!(super::recall_at_k(&ranked, &relevance, 10), 2.0 / 3.0);
+        assert_eq!(super::precision_at_k(&ranked, &relevance, 2), 0.5);
+        assert_eq!(super::precision_at_k(&ranked, &relevance, 8), 0.25);
+        assert_eq!(super::reciprocal_rank(&ranked, &relevance), 0.5);
+        assert_eq!(
+            super::average_precision(&ranked, &relevance),
+            (0.5 + 0.5) / 3.0
+        );
+        assert_eq!(super::reciprocal_rank(&["a"], &relevance), 0.0);
+        let ideal = 3.0 + 1.0 / 3f64.log2() + 1.0 / 4f64.log2();
+        let actual = 1.0 / 3f64.log2() + 3.0 / 5f64.log2();
+        assert_eq!(
+            super::ndcg_at_k(&ranked, &relevance, 4),
+            (actual / ideal) as f32
+        );
+        assert!((super::ndcg_at_k(&["d", "b", "e"], &relevance, 3) - 1.0).abs() < 1e-6);
+        assert_eq!(super::ndcg_at_k(&ranked, &HashMap::new(), 3), 0.0);
+    }
+
+    #[test]
+    fn test_metrics_duplicates() {
+        let ranked = ["b", "b", "a", "d", "d"];
+        let relevance = HashMap::from([("b", 1), ("d", 2), ("e", 1)]);
+        assert_eq!(super::recall_at_k(&ranked, &relevance, 2), 1.0 / 3.0);
+        assert_eq!(super::recall_at_k(&ranked, &relevance, 10), 2.0 / 3.0);
+        assert_eq!(super::precision_at_k(&ranked, &relevance, 3), 2.0 / 3.0);
+        assert_eq!(
+            super::average_precision(&ranked, &relevance),
+            (1.0 + 2.0 / 3.0) / 3.0
+        );
+        let ideal = 3.0 + 1.0 / 3f64.log2() + 1.0 / 4f64.log2();
+        let actual = 1.0 + 3.0 / 4f64.log2();
+        assert_eq!(
+            super::ndcg_at_k(&ranked, &relevance, 5),
+            (actual / ideal) as f32
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matches {
+    pub phrases: u32,
+    pub proximity: f32,
+}
+
+impl Matches {
+    pub fn score(&self) -> f32 {
+        self.phrases as f32 + self.proximity
+    }
+}
+
+fn positions(tokens: &[String]) -> HashMap<&str, Vec<u32>> {
+    let mut result = HashMap::<&str, Vec<u32>>::new();
+    for (position, token) in tokens.iter().enumerate() {
+        result.entry(token).or_default().push(position as u32);
+    }
+    result
+}
+
+// both lists are sorted
+fn min_distance(a: &[u32], b: &[u32]) -> Option<u32> {
+    let (mut i, mut j) = (0, 0);
+    let mut result = None::<u32>;
+    while i < a.len() && j < b.len() {
+        let distance = a[i].abs_diff(b[j]);
+        result = Some(result.map_or(distance, |r| r.min(distance)));
+        if a[i] < b[j] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    result
+}
+
+/// Counts exact occurrences of `query` in `document`, and scores each pair of adjacent query
+/// tokens by how close they occur, from 1 for neighbours down to `1 / window` for tokens
+/// `window` positions apart.
+pub fn matches(document: &[String], query: &[String], window: u32) -> Matches {
+    let phrases = if query.is_empty() {
+        0
+    } else {
+        document
+            .windows(query.len())
+            .filter(|w| *w == query)
+            .count() as u32
+    };
+    let positions = positions(document);
+    let mut proximity = 0.0;
+    for pair in query.windows(2) {
+        if pair[0] == pair[1] {
+            continue;
+        }
+        let (Some(a), Some(b)) = (
+            positions.get(pair[0].as_str()),
+            positions.get(pair[1].as_str()),
+        ) else {
+            continue;
+        };
+        if let Some(distance) = min_distance(a, b).filter(|d| *d <= window) {
+            proximity += (window - distance + 1) as f32 / window as f32;
+        }
+    }
+    Matches { phrases, proximity }
+}
+
+#[cfg(test)]
+mod tests {
+    fn tokens(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_matches() {
+        let document = tokens("machine learning is learning by a machine , machine learning");
+        let m = super::matches(&document, &tokens("machine learning"), 8);
+        assert_eq!(m.phrases, 2);
+        assert_eq!(m.proximity, 1.0);
+
+        let m = super::matches(&document, &tokens("machine by"), 4);
+        assert_eq!(m.phrases, 0);
+        assert_eq!(m.proximity, 0.75);
+
+        let m = super::matches(&document, &tokens("deep learning"), 8);
+        assert_eq!(m.score(), 0.0);
+    }
+}
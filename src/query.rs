@@ -0,0 +1,252 @@
+//! Query syntax: `+term` is required, `-term` is excluded, `term^2` is boosted, `"a phrase"` is
+//! matched as a phrase and `(a OR b)` matches any of its clauses. A `+` or `-` followed by a
+//! space is a term itself.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Occur {
+    Should,
+    Must,
+    MustNot,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Text(String),
+    Phrase(String),
+    Group(Vec<Clause>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clause {
+    pub occur: Occur,
+    pub node: Node,
+    pub boost: f32,
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.bump();
+        }
+        &self.s[start..self.pos]
+    }
+
+    fn clauses(&mut self, nested: bool) -> Result<Vec<Clause>, String> {
+        let mut result = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None if nested => return Err("missing `)`".to_string()),
+                None => return Ok(result),
+                Some(')') if nested => {
+                    self.bump();
+                    return Ok(result);
+                }
+                Some(')') => return Err(format!("unexpected `)` at {}", self.pos)),
+                Some(_) => {
+                    if let Some(clause) = self.clause()? {
+                        result.push(clause);
+                    }
+                }
+            }
+        }
+    }
+
+    fn clause(&mut self) -> Result<Option<Clause>, String> {
+        // a `+` or `-` not followed by a clause is a term itself
+        let operand = self.s[self.pos..]
+            .chars()
+            .nth(1)
+            .is_some_and(|c| !c.is_whitespace() && c != ')');
+        let occur = match self.peek() {
+            Some('+') if operand => Occur::Must,
+            Some('-') if operand => Occur::MustNot,
+            _ => Occur::Should,
+        };
+        if occur != Occur::Should {
+            self.bump();
+        }
+        let node = match self.peek() {
+            Some('"') => {
+                let start = self.pos;
+                self.bump();
+                let phrase = self.take_while(|c| c != '"').to_string();
+                if self.bump().is_none() {
+                    return Err(format!("missing `\"` for the phrase at {start}"));
+                }
+                Node::Phrase(phrase)
+            }
+            Some('(') => {
+                self.bump();
+                Node::Group(self.clauses(true)?)
+            }
+            _ => {
+                let word = self.take_while(|c| !c.is_whitespace() && !"()\"^".contains(c));
+                match word {
+                    // clauses are optional anyway, so `OR` is only a separator
+                    "OR" if occur == Occur::Should => return Ok(None),
+                    "" => return Err(format!("expected a term at {}", self.pos)),
+                    word => Node::Text(word.to_string()),
+                }
+            }
+        };
+        let mut boost = 1.0;
+        if self.peek() == Some('^') {
+            self.bump();
+            let number = self.take_while(|c| c.is_ascii_digit() || c == '.');
+            boost = number
+                .parse::<f32>()
+                .map_err(|_| format!("invalid boost `{number}` at {}", self.pos))?;
+        }
+        Ok(Some(Clause { occur, node, boost }))
+    }
+}
+
+pub fn parse(s: &str) -> Result<Vec<Clause>, String> {
+    Parser { s, pos: 0 }.clauses(false)
+}
+
+/// The texts that contribute to the query vector, with their boosts. Excluded clauses do not
+/// contribute.
+pub fn weighted_texts(clauses: &[Clause]) -> Vec<(&str, f32)> {
+    let mut result = Vec::new();
+    for clause in clauses.iter().filter(|c| c.occur != Occur::MustNot) {
+        match &clause.node {
+            Node::Text(text) | Node::Phrase(text) => result.push((text.as_str(), clause.boost)),
+            Node::Group(group) => result.extend(
+                weighted_texts(group)
+                    .into_iter()
+                    .map(|(text, boost)| (text, boost * clause.boost)),
+            ),
+        }
+    }
+    result
+}
+
+/// Checks required and excluded clauses. `contains` tells whether the document contains a text
+/// as a term or a phrase.
+pub fn filter(clauses: &[Clause], contains: &mut impl FnMut(&str) -> bool) -> bool {
+    clauses.iter().all(|clause| match clause.occur {
+        Occur::Should => true,
+        Occur::Must => matches(&clause.node, contains),
+        Occur::MustNot => !matches(&clause.node, contains),
+    })
+}
+
+fn matches(node: &Node, contains: &mut impl FnMut(&str) -> bool) -> bool {
+    match node {
+        Node::Text(text) | Node::Phrase(text) => contains(text),
+        Node::Group(group) => group.iter().any(|clause| match clause.occur {
+            Occur::Should | Occur::Must => matches(&clause.node, contains),
+            Occur::MustNot => !matches(&clause.node, contains),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clause, Node, Occur};
+
+    fn text(occur: Occur, text: &str, boost: f32) -> Clause {
+        Clause {
+            occur,
+            node: Node::Text(text.to_string()),
+            boost,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            super::parse("+apple -pear state-of-the-art^2.5 \"machine learning\" (a OR b)"),
+            Ok(vec![
+                text(Occur::Must, "apple", 1.0),
+                text(Occur::MustNot, "pear", 1.0),
+                text(Occur::Should, "state-of-the-art", 2.5),
+                Clause {
+                    occur: Occur::Should,
+                    node: Node::Phrase("machine learning".to_string()),
+                    boost: 1.0,
+                },
+                Clause {
+                    occur: Occur::Should,
+                    node: Node::Group(vec![
+                        text(Occur::Should, "a", 1.0),
+                        text(Occur::Should, "b", 1.0)
+                    ]),
+                    boost: 1.0,
+                },
+            ])
+        );
+        assert!(super::parse("(a b").is_err());
+        assert!(super::parse("a)").is_err());
+        assert!(super::parse("a^x").is_err());
+        assert!(super::parse("a \"machine learning").is_err());
+    }
+
+    #[test]
+    fn test_parse_operator_literal() {
+        assert_eq!(
+            super::parse("a - b + (c -)"),
+            Ok(vec![
+                text(Occur::Should, "a", 1.0),
+                text(Occur::Should, "-", 1.0),
+                text(Occur::Should, "b", 1.0),
+                text(Occur::Should, "+", 1.0),
+                Clause {
+                    occur: Occur::Should,
+                    node: Node::Group(vec![
+                        text(Occur::Should, "c", 1.0),
+                        text(Occur::Should, "-", 1.0)
+                    ]),
+                    boost: 1.0,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_weighted_texts() {
+        let clauses = super::parse("a -b (c d^3)^2").unwrap();
+        assert_eq!(
+            super::weighted_texts(&clauses),
+            vec![("a", 1.0), ("c", 2.0), ("d", 6.0)]
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let document = ["apple", "banana"];
+        let mut contains = |t: &str| document.contains(&t);
+        let clauses = super::parse("+apple -cherry pear").unwrap();
+        assert!(super::filter(&clauses, &mut contains));
+        let clauses = super::parse("+apple -banana").unwrap();
+        assert!(!super::filter(&clauses, &mut contains));
+        let clauses = super::parse("+(cherry OR banana)").unwrap();
+        assert!(super::filter(&clauses, &mut contains));
+        let clauses = super::parse("+(cherry OR pear)").unwrap();
+        assert!(!super::filter(&clauses, &mut contains));
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Variants of inverse document frequency, computed from the number of documents and the number
+/// of documents containing a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Idf {
+    /// `ln((N + 1) / (df + 0.5))`
+    Bm25,
+    /// `ln(1 + (N - df + 0.5) / (df + 0.5))`
+    Lucene,
+    /// `ln((N - df + 0.5) / (df + 0.5))`, floored at zero for tokens in more than half the documents
+    Robertson,
+    /// `ln((N + 1) / (df + 1)) + 1`
+    Smooth,
+}
+
+impl Idf {
+    pub fn parse(s: &str) -> Result<Idf, String> {
+        match s {
+            "bm25" => Ok(Idf::Bm25),
+            "lucene" => Ok(Idf::Lucene),
+            "robertson" => Ok(Idf::Robertson),
+            "smooth" => Ok(Idf::Smooth),
+            _ => Err(format!("unknown idf: {s}")),
+        }
+    }
+
+    pub fn idf(&self, docs: i32, df: i32) -> f32 {
+        let (docs, df) = (docs as f64, df as f64);
+        let idf = match self {
+            Idf::Bm25 => ((docs + 1.0) / (df + 0.5)).ln(),
+            Idf::Lucene => (1.0 + (docs - df + 0.5) / (df + 0.5)).ln(),
+            Idf::Robertson => ((docs - df + 0.5) / (df + 0.5)).ln().max(0.0),
+            Idf::Smooth => ((docs + 1.0) / (df + 1.0)).ln() + 1.0,
+        };
+        idf as f32
+    }
+}
+
+fn norm(length: u32, b: f32, k1: f32, words: i32, docs: i32) -> f32 {
+    let avgdl = words as f32 / docs as f32;
+    k1 * ((1.0 - b) + b * (length as f32 / avgdl))
+}
+
+/// BM25 weight of each dimension of a document, and the length normalization factor.
+pub fn document_weights(
+    x: &BTreeMap<u32, u32>,
+    b: f32,
+    k1: f32,
+    words: i32,
+    docs: i32,
+) -> (f32, BTreeMap<u32, f32>) {
+    let norm = norm(x.values().sum(), b, k1, words, docs);
+    let weights = x
+        .iter()
+        .map(|(index, value)| (*index, *value as f32 / (*value as f32 + norm)))
+        .collect();
+    (norm, weights)
+}
+
+/// Weight of each dimension of a query. A query without positive weights, e.g. of tokens whose
+/// `robertson` idf is floored at zero, has no dimensions.
+pub fn query_weights(x: BTreeMap<u32, f32>) -> BTreeMap<u32, f32> {
+    // https://github.com/pinecone-io/pinecone-text/issues/69
+    let sum = x.values().copied().sum::<f32>();
+    if sum <= 0.0 {
+        return BTreeMap::new();
+    }
+    x.into_iter()
+        .map(|(index, value)| (index, value / sum))
+        .collect()
+}
+
+/// Largest weight a token can have in a document. Weights increase with tf, and tf is at most the
+/// number of occurrences of the token in the corpus.
+pub fn max_weight(cf: i32, b: f32, k1: f32, words: i32, docs: i32) -> f32 {
+    if cf <= 0 {
+        return 0.0;
+    }
+    let norm = norm(cf as u32, b, k1, words, docs);
+    cf as f32 / (cf as f32 + norm)
+}
+
+/// Scales a vector to unit length.
+pub fn l2_normalize(x: BTreeMap<u32, f32>) -> BTreeMap<u32, f32> {
+    let length = x.values().map(|v| v * v).sum::<f32>().sqrt();
+    if length == 0.0 {
+        return x;
+    }
+    x.into_iter()
+        .map(|(index, value)| (index, value / length))
+        .collect()
+}
+
+/// Maps a score to `(0, 1)`, with `midpoint` mapped to 0.5.
+pub fn sigmoid(score: f32, midpoint: f32, slope: f32) -> f32 {
+    1.0 / (1.0 + (-slope * (score - midpoint)).exp())
+}
+
+/// Fits `sigmoid` to relevance labels of scores by logistic regression, as in Platt scaling, and
+/// returns the midpoint and the slope. There is no fit unless both labels occur and scores of
+/// relevant documents tend to be higher.
+pub fn fit_sigmoid(scores: &[f32], labels: &[bool]) -> Option<(f32, f32)> {
+    let positives = labels.iter().filter(|l| **l).count() as f64;
+    let negatives = labels.len() as f64 - positives;
+    if positives == 0.0 || negatives == 0.0 {
+        return None;
+    }
+    // soft targets keep the slope finite when the labels are separable
+    let high = (positives + 1.0) / (positives + 2.0);
+    let low = 1.0 / (negatives + 2.0);
+    let data = scores
+        .iter()
+        .zip(labels)
+        .map(|(s, l)| (*s as f64, if *l { high } else { low }))
+        .collect::<Vec<_>>();
+    let loss = |a: f64, c: f64| {
+        data.iter()
+            .map(|(s, t)| {
+                let z = a * s + c;
+                // log(1 + e^z) - t z, computed without overflow
+                z.max(0.0) + (-z.abs()).exp().ln_1p() - t * z
+            })
+            .sum::<f64>()
+    };
+    let (mut a, mut c) = (0.0, ((positives + 1.0) / (negatives + 1.0)).ln());
+    for _ in 0..100 {
+        let (mut ga, mut gc, mut haa, mut hac, mut hcc) = (0.0, 0.0, 1e-12, 0.0, 1e-12);
+        for (s, t) in data.iter() {
+            let p = 1.0 / (1.0 + (-(a * s + c)).exp());
+            let w = p * (1.0 - p);
+            ga += (p - t) * s;
+            gc += p - t;
+            haa += w * s * s;
+            hac += w * s;
+            hcc += w;
+        }
+        let det = haa * hcc - hac * hac;
+        let (da, dc) = ((hcc * ga - hac * gc) / det, (haa * gc - hac * ga) / det);
+        let current = loss(a, c);
+        let mut step = 1.0;
+        while step > 1e-10 && loss(a - step * da, c - step * dc) > current {
+            step /= 2.0;
+        }
+        a -= step * da;
+        c -= step * dc;
+        if (step * da).abs() < 1e-9 && (step * dc).abs() < 1e-9 {
+            break;
+        }
+    }
+    if a.is_nan() || a <= 0.0 {
+        return None;
+    }
+    Some(((-c / a) as f32, a as f32))
+}
+
+/// Term frequencies of a set of documents, to rank them with different `b` and `k1` without
+/// building document vectors.
+#[derive(Debug, Default)]
+pub struct Index {
+    lengths: Vec<u32>,
+    postings: HashMap<u32, Vec<(u32, u32)>>,
+}
+
+impl Index {
+    /// Adds a document, numbered in the order of insertion.
+    pub fn push(&mut self, x: &BTreeMap<u32, u32>) {
+        let document = self.lengths.len() as u32;
+        self.lengths.push(x.values().sum());
+        for (index, value) in x.iter() {
+            self.postings
+                .entry(*index)
+                .or_default()
+                .push((document, *value));
+        }
+    }
+
+    /// The `k` documents with the highest dot product of their weights with `query`, best first.
+    pub fn top_k(
+        &self,
+        query: &BTreeMap<u32, f32>,
+        b: f32,
+        k1: f32,
+        words: i32,
+        docs: i32,
+        k: usize,
+    ) -> Vec<u32> {
+        let mut scores = HashMap::<u32, f32>::new();
+        for (index, weight) in query.iter() {
+            for (document, value) in self.postings.get(index).into_iter().flatten() {
+                let norm = norm(self.lengths[*document as usize], b, k1, words, docs);
+                *scores.entry(*document).or_default() +=
+                    weight * *value as f32 / (*value as f32 + norm);
+            }
+        }
+        let mut scores = scores.into_iter().collect::<Vec<_>>();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores
+            .into_iter()
+            .take(k)
+            .map(|(document, _)| document)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Idf;
+
+    #[test]
+    fn test_idf() {
+        assert_eq!(Idf::Bm25.idf(2, 2), (3.0f64 / 2.5).ln() as f32);
+        assert_eq!(Idf::Lucene.idf(2, 2), (1.0f64 + 0.5 / 2.5).ln() as f32);
+        assert_eq!(Idf::Robertson.idf(2, 2), 0.0);
+        assert_eq!(Idf::Smooth.idf(2, 2), 1.0);
+        assert!(Idf::parse("tfidf").is_err());
+    }
+
+    #[test]
+    fn test_weights() {
+        let x = [(0, 2), (3, 1)].into_iter().collect();
+        let (norm, weights) = super::document_weights(&x, 0.75, 1.2, 6, 2);
+        assert_eq!(norm, 1.2);
+        assert_eq!(weights[&0], 2.0 / 3.2);
+        assert_eq!(weights[&3], 1.0 / 2.2);
+        let x = [(0, 1.0), (3, 3.0)].into_iter().collect();
+        let weights = super::query_weights(x);
+        assert_eq!(weights[&0], 0.25);
+        assert_eq!(weights[&3], 0.75);
+        let x = [(0, 0.0), (3, 0.0)].into_iter().collect();
+        assert!(super::query_weights(x).is_empty());
+    }
+
+    #[test]
+    fn test_normalization() {
+        // tf = cf = 2 in a document of length 2 and avgdl 2
+        assert_eq!(super::max_weight(2, 0.75, 1.2, 4, 2), 2.0 / 3.2);
+        assert_eq!(super::max_weight(0, 0.75, 1.2, 4, 2), 0.0);
+        let x = super::l2_normalize([(0, 3.0), (2, 4.0)].into_iter().collect());
+        assert_eq!(x.values().copied().collect::<Vec<_>>(), vec![0.6, 0.8]);
+        assert_eq!(super::sigmoid(0.3, 0.3, 10.0), 0.5);
+        assert!(super::sigmoid(0.9, 0.3, 10.0) > 0.99);
+        let scores = [0.1, 0.2, 0.3, 0.45, 0.55, 0.7, 0.8, 0.9];
+        let labels = [false, false, false, true, false, true, true, true];
+        let (midpoint, slope) = super::fit_sigmoid(&scores, &labels).unwrap();
+        assert!((midpoint - 0.5).abs() < 0.05 && slope > 0.0);
+        assert_eq!(super::fit_sigmoid(&scores, &[true; 8]), None);
+    }
+
+    #[test]
+    fn test_index() {
+        let mut index = super::Index::default();
+        index.push(&[(0, 1), (1, 1)].into_iter().collect());
+        index.push(&[(0, 1), (1, 3)].into_iter().collect());
+        index.push(&[(2, 2)].into_iter().collect());
+        let query = [(0, 0.5), (1, 0.5)].into_iter().collect();
+        assert_eq!(index.top_k(&query, 0.0, 1.2, 8, 3, 10), vec![1, 0]);
+        // a strong length penalty prefers the short document
+        assert_eq!(index.top_k(&query, 1.0, 5.0, 8, 3, 1), vec![0]);
+    }
+}
@@ -3,121 +3,1130 @@ CREATE FUNCTION array_distinct(anyarray) RETURNS anyarray IMMUTABLE STRICT PARAL
 $fn$ LANGUAGE SQL;
 
 CREATE TABLE pg_bm25(
-    -- classes
-    attrelid regclass NOT NULL,
-    attname NAME NOT NULL,
-    matrelid regclass UNIQUE,
-    indexrelid regclass UNIQUE,
+    -- classes, null for statistics without a table, see `bm25_import`
+    attrelid regclass,
+    attname NAME,
+    -- follows renames of the column, see `bm25_ddl_command_end`
+    attnum SMALLINT,
+    matrelid regclass PRIMARY KEY,
+    -- the current generation and its index, behind the view `matrelid`
+    genrelid regclass UNIQUE,
+    -- not a regclass, since a dump restores indexes after the catalog, see `bm25_restore`
+    indexrelid oid NOT NULL,
     -- props
     b REAL NOT NULL,
     k1 REAL NOT NULL,
+    idf TEXT NOT NULL,
     tokenizer TEXT NOT NULL,
     model TEXT NOT NULL,
+    vocabulary TEXT NOT NULL,
+    hash_bits INT,
+    hash_seed INT,
+    -- statistics whose counts are added to those of the table, times the weight
+    background regclass,
+    background_weight REAL,
     -- cached
     words INT NOT NULL,
     docs INT NOT NULL,
-    dims INT NOT NULL
+    dims INT NOT NULL,
+    generation INT NOT NULL,
+    refreshed_at TIMESTAMPTZ NOT NULL,
+    -- changes of the table counted by the statistics collector at the last refresh, plus the rows
+    -- updated by re-embedding since, which do not change the text
+    baseline_changes BIGINT NOT NULL DEFAULT 0,
+    -- maintenance by the background worker
+    maintained_at TIMESTAMPTZ,
+    maintenance_error TEXT
+);
+
+-- Retained generations of statistics, including the current one.
+CREATE TABLE pg_bm25_generations(
+    matrelid regclass NOT NULL,
+    generation INT NOT NULL,
+    genrelid regclass UNIQUE,
+    indexrelid oid NOT NULL,
+    words INT NOT NULL,
+    docs INT NOT NULL,
+    dims INT NOT NULL,
+    -- scoring parameters while the generation was current, which document vectors embedded with it bake in
+    b REAL NOT NULL,
+    k1 REAL NOT NULL,
+    idf TEXT NOT NULL,
+    pinned BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (matrelid, generation)
 );
 
-CREATE FUNCTION bm25_create(tab regclass, col TEXT, mat TEXT, tokenizer TEXT DEFAULT 'hf', model TEXT DEFAULT 'google-bert/bert-base-uncased', b REAL DEFAULT 0.75, k1 REAL DEFAULT 1.2) RETURNS VOID AS $fn$
+-- Columns of document vectors and the generation they were embedded with. Attached columns have a
+-- key, and are re-embedded in batches of rows in key order whenever they are pending.
+CREATE TABLE pg_bm25_columns(
+    matrelid regclass NOT NULL,
+    attrelid regclass NOT NULL,
+    attname NAME NOT NULL,
+    source NAME NOT NULL,
+    style TEXT NOT NULL,
+    keyname NAME,
+    -- numbers of `attname`, `source` and `keyname`, which follow renames
+    attnum SMALLINT NOT NULL,
+    sourcenum SMALLINT NOT NULL,
+    keynum SMALLINT,
+    -- null until the column is embedded once
+    generation INT,
+    embedded_at TIMESTAMPTZ,
+    -- the generation being embedded, and the last key of the rows done so far
+    pending INT,
+    last_key TEXT,
+    PRIMARY KEY (attrelid, attname)
+);
+
+SELECT pg_catalog.pg_extension_config_dump('bm_catalog.pg_bm25', '');
+SELECT pg_catalog.pg_extension_config_dump('bm_catalog.pg_bm25_generations', '');
+SELECT pg_catalog.pg_extension_config_dump('bm_catalog.pg_bm25_columns', '');
+
+CREATE FUNCTION bm25_attnum(tab regclass, col NAME) RETURNS SMALLINT STABLE STRICT AS $fn$
+    SELECT a.attnum FROM pg_catalog.pg_attribute a WHERE a.attrelid = tab AND a.attname = col AND a.attnum > 0 AND NOT a.attisdropped;
+$fn$ LANGUAGE SQL;
+
+-- The relation named `mat` with `suffix` appended to its name, quoted and qualified with the schema
+-- of `mat`, or the current schema if `mat` has none.
+CREATE FUNCTION bm25_name(mat TEXT, suffix TEXT DEFAULT '') RETURNS TEXT STABLE STRICT AS $fn$
+    SELECT format('%I.%I', CASE WHEN cardinality(n) > 1 THEN n[cardinality(n) - 1] ELSE current_schema() END, n[cardinality(n)] || suffix)
+    FROM (SELECT pg_catalog.parse_ident(mat) AS n) x;
+$fn$ LANGUAGE SQL;
+
+-- The index of a generation, its only index.
+CREATE FUNCTION bm25_index_of(gen regclass) RETURNS oid STABLE STRICT AS $fn$
+    SELECT i.indexrelid FROM pg_catalog.pg_index i WHERE i.indrelid = gen ORDER BY i.indexrelid LIMIT 1;
+$fn$ LANGUAGE SQL;
+
+-- Inserted, updated and deleted rows of a table, as counted by the statistics collector.
+CREATE FUNCTION bm25_table_changes(tab regclass) RETURNS BIGINT STABLE STRICT AS $fn$
+    SELECT coalesce((SELECT n_tup_ins + n_tup_upd + n_tup_del FROM pg_catalog.pg_stat_all_tables WHERE relid = tab), 0);
+$fn$ LANGUAGE SQL;
+
+-- Changes of the table of each statistics since the last refresh, and whether the background worker
+-- refreshes it in its next round. The counts lag behind by up to a few seconds. After a reset of the
+-- statistics collector or a crash, which restart the counts below the baseline, the changes are
+-- those since the reset, and `bm25_maintain` resets the baseline.
+CREATE VIEW pg_bm25_status AS
+    SELECT p.matrelid, p.attrelid, p.attname, p.docs, p.generation, p.refreshed_at, c.changes,
+        c.changes >= t.threshold * greatest(p.docs, 1) AND t.threshold > 0 AS due,
+        (SELECT count(*) FROM bm_catalog.pg_bm25_columns x WHERE x.matrelid = p.matrelid AND x.pending IS NOT NULL) AS pending_columns,
+        p.maintained_at, p.maintenance_error
+    FROM bm_catalog.pg_bm25 p
+    CROSS JOIN LATERAL (SELECT coalesce(bm_catalog.bm25_table_changes(p.attrelid), 0) AS total) n
+    CROSS JOIN LATERAL (SELECT CASE WHEN n.total < p.baseline_changes THEN n.total ELSE n.total - p.baseline_changes END AS changes) c
+    CROSS JOIN (SELECT coalesce(nullif(current_setting('pg_bestmatch.refresh_threshold', true), '')::REAL, 0.1) AS threshold) t;
+
+-- Builds a generation of statistics, the table `gen` and its index. The counts of the
+-- current generation of `background` are added, times `background_weight`. The terms of `col` are
+-- counted by `bm25_count_terms` in one scan of `tab`. The counts are stored rather than derived from
+-- `tab`, so that dumps and publications carry each generation as it was built.
+CREATE FUNCTION bm25_build(gen TEXT, tab regclass, col TEXT, tokenizer TEXT, model TEXT, vocabulary TEXT, hash_bits INT, hash_seed INT, background regclass DEFAULT NULL, background_weight REAL DEFAULT 1) RETURNS VOID AS $fn$
+DECLARE
+    counts TEXT;
+    blend TEXT := '';
+BEGIN
+    IF bm_catalog.bm25_attnum(tab, col) IS NULL THEN
+        RAISE EXCEPTION 'Column `%` of `%` does not exist.', col, tab;
+    END IF;
+    IF vocabulary NOT IN ('corpus', 'fixed', 'hashed') THEN
+        RAISE EXCEPTION 'Unknown vocabulary `%`.', vocabulary;
+    END IF;
+    counts := format(
+        'bm_catalog.bm25_count_terms(%L::regclass, %L, %L, %L, %L, %s, %s)',
+        tab, col, tokenizer, model, vocabulary, coalesce(hash_bits, 0), coalesce(hash_seed, 0)
+    );
+    IF background IS NOT NULL THEN
+        -- terms whose weighted counts round to zero stay out
+        blend := format(
+            'UNION ALL SELECT %s, round(%s * how_many_tokens)::INT, round(%s * token_in_how_many_inputs)::INT FROM %s WHERE round(%s * token_in_how_many_inputs) > 0',
+            CASE WHEN vocabulary = 'corpus' THEN 'token' ELSE 'id' END, background_weight, background_weight, background, background_weight
+        );
+    END IF;
+    IF vocabulary = 'corpus' THEN
+        EXECUTE format($$
+            CREATE TABLE %s AS
+                WITH counts AS (
+                    SELECT token::NAME COLLATE "C" AS token, how_many_tokens, token_in_how_many_inputs
+                    FROM %s
+                    %s
+                )
+                SELECT
+                    token,
+                    (row_number() OVER (ORDER BY token) - 1)::INT AS id,
+                    sum(how_many_tokens)::INT AS how_many_tokens,
+                    sum(token_in_how_many_inputs)::INT AS token_in_how_many_inputs
+                FROM counts
+                GROUP BY token
+                ORDER BY token;
+            CREATE INDEX ON %s(token);
+        $$, gen, counts, blend, gen);
+    ELSE
+        EXECUTE format($$
+            CREATE TABLE %s AS
+                WITH counts AS (
+                    SELECT id, how_many_tokens, token_in_how_many_inputs
+                    FROM %s
+                    %s
+                )
+                SELECT
+                    id,
+                    sum(how_many_tokens)::INT AS how_many_tokens,
+                    sum(token_in_how_many_inputs)::INT AS token_in_how_many_inputs
+                FROM counts
+                GROUP BY id;
+            CREATE INDEX ON %s(id);
+        $$, gen, counts, blend, gen);
+    END IF;
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_create(tab regclass, col TEXT, mat TEXT, tokenizer TEXT DEFAULT bm_catalog.bm25_default('tokenizer'), model TEXT DEFAULT bm_catalog.bm25_default('model'), b REAL DEFAULT bm_catalog.bm25_default('b')::REAL, k1 REAL DEFAULT bm_catalog.bm25_default('k1')::REAL, vocabulary TEXT DEFAULT 'corpus', hash_bits INT DEFAULT 20, hash_seed INT DEFAULT 0, idf TEXT DEFAULT 'bm25', background regclass DEFAULT NULL, background_weight REAL DEFAULT 1) RETURNS VOID AS $fn$
 DECLARE
     test TEXT;
+    gen TEXT;
     ins_words INT;
     ins_docs INT;
     ins_dims INT;
+    bg bm_catalog.pg_bm25;
 BEGIN
     SELECT 'ok' INTO test FROM pg_catalog.pg_attribute WHERE attrelid = tab AND attname = col AND atttypid = 'text'::regtype;
     IF test != 'ok' THEN
         RAISE EXCEPTION 'This is no such table or no such column or column is not of type `text`.';
     END IF;
-    EXECUTE format($$
-        CREATE MATERIALIZED VIEW %s AS
-            WITH
-                inputs AS (SELECT bm_catalog.tokenize(%s, %L, %L) AS input FROM %s),
-                tokens AS (SELECT unnest(input)::NAME COLLATE "C" AS token FROM inputs GROUP BY token ORDER BY token),
-                compute_how_many_tokens AS (
-                    SELECT unnest(input)::NAME COLLATE "C" AS t, count(*)::INT AS how_many_tokens
-                    FROM inputs
-                    GROUP BY t
-                    ORDER BY t
-                ),
-                compute_token_in_how_many_inputs AS (
-                    SELECT unnest(bm_catalog.array_distinct(input))::NAME COLLATE "C" AS t, count(*)::INT AS token_in_how_many_inputs
-                    FROM inputs
-                    GROUP BY t
-                    ORDER BY t
-                ),
-                var_docs AS (SELECT count(*) AS docs FROM inputs)
-            SELECT
-                token,
-                (row_number() OVER () - 1)::INT AS id,
-                how_many_tokens,
-                ln((docs + 1.0) / (token_in_how_many_inputs + 0.5))::REAL AS idf
-            FROM tokens
-            JOIN compute_how_many_tokens ON compute_how_many_tokens.t = token
-            JOIN compute_token_in_how_many_inputs ON compute_token_in_how_many_inputs.t = token
-            CROSS JOIN var_docs;
-        CREATE INDEX %s_index ON %s(token);
-    $$, mat, col, tokenizer, model, tab, mat, mat);
-    EXECUTE format('SELECT sum(how_many_tokens) FROM %s', mat) INTO ins_words;
-    EXECUTE format('SELECT count(%s) FROM %s', col, tab) INTO ins_docs;
-    EXECUTE format('SELECT count(*) FROM %s', mat) INTO ins_dims;
-    INSERT INTO bm_catalog.pg_bm25
-    VALUES (tab, col, mat::regclass, (mat::text || '_index')::regclass, b, k1, tokenizer, model, ins_words, ins_docs, ins_dims);
+    IF b < 0 OR b > 1 OR k1 < 0 THEN
+        RAISE EXCEPTION 'BM25 parameters must satisfy 0 <= b <= 1 and k1 >= 0.';
+    END IF;
+    -- raises an error for an unknown variant
+    PERFORM bm_catalog.bm25_idf(idf, 1, 1);
+    IF vocabulary = 'corpus' THEN
+        hash_bits := NULL;
+        hash_seed := NULL;
+    ELSIF vocabulary = 'fixed' THEN
+        -- token ids of the tokenizer are the dimensions
+        IF bm_catalog.has_bigrams(model) THEN
+            RAISE EXCEPTION 'Bigrams have no native token ids, use vocabulary `corpus` or `hashed` with the `bigrams` option.';
+        END IF;
+        ins_dims := bm_catalog.vocab_size(tokenizer, model);
+        hash_bits := NULL;
+        hash_seed := NULL;
+    ELSIF vocabulary = 'hashed' THEN
+        -- hash buckets of tokens are the dimensions
+        IF hash_bits IS NULL OR hash_bits NOT BETWEEN 1 AND 30 THEN
+            RAISE EXCEPTION 'Hash bits must be between 1 and 30.';
+        END IF;
+        ins_dims := 1 << hash_bits;
+    ELSE
+        RAISE EXCEPTION 'Unknown vocabulary `%`.', vocabulary;
+    END IF;
+    IF background IS NOT NULL THEN
+        SELECT * INTO bg FROM bm_catalog.pg_bm25 x WHERE x.matrelid = bm25_create.background;
+        IF NOT FOUND THEN
+            RAISE EXCEPTION 'There is no BM25 statistics `%`.', background;
+        END IF;
+        -- counts only add up over the same terms
+        IF (bg.tokenizer, bg.model, bg.vocabulary) IS DISTINCT FROM (tokenizer, model, vocabulary) OR (bg.hash_bits, bg.hash_seed) IS DISTINCT FROM (hash_bits, hash_seed) THEN
+            RAISE EXCEPTION 'Background statistics `%` must have the same tokenizer, model and vocabulary.', background;
+        END IF;
+        IF background_weight IS NULL OR background_weight < 0 THEN
+            RAISE EXCEPTION 'Background weight must be at least 0.';
+        END IF;
+    ELSE
+        background_weight := NULL;
+    END IF;
+    gen := bm_catalog.bm25_name(mat, '_g1');
+    PERFORM bm_catalog.bm25_build(gen, tab, col, tokenizer, model, vocabulary, hash_bits, hash_seed, background, background_weight);
+    EXECUTE format('CREATE VIEW %s AS SELECT * FROM %s', bm_catalog.bm25_name(mat), gen);
+    IF vocabulary = 'corpus' THEN
+        EXECUTE format('SELECT count(*) FROM %s', gen) INTO ins_dims;
+    END IF;
+    EXECUTE format('SELECT coalesce(sum(how_many_tokens), 0) FROM %s', gen) INTO ins_words;
+    EXECUTE format('SELECT count(%I) FROM %s', col, tab) INTO ins_docs;
+    ins_docs := ins_docs + coalesce(round(background_weight * bg.docs)::INT, 0);
+    INSERT INTO bm_catalog.pg_bm25 (attrelid, attname, attnum, matrelid, genrelid, indexrelid, b, k1, idf, tokenizer, model, vocabulary, hash_bits, hash_seed, background, background_weight, words, docs, dims, generation, refreshed_at, baseline_changes)
+    VALUES (tab, col, bm_catalog.bm25_attnum(tab, col), bm_catalog.bm25_name(mat)::regclass, gen::regclass, bm_catalog.bm25_index_of(gen::regclass), b, k1, idf, tokenizer, model, vocabulary, hash_bits, hash_seed, background, background_weight, ins_words, ins_docs, ins_dims, 1, now(), bm_catalog.bm25_table_changes(tab));
+    INSERT INTO bm_catalog.pg_bm25_generations (matrelid, generation, genrelid, indexrelid, words, docs, dims, b, k1, idf, created_at)
+    VALUES (bm_catalog.bm25_name(mat)::regclass, 1, gen::regclass, bm_catalog.bm25_index_of(gen::regclass), ins_words, ins_docs, ins_dims, b, k1, idf, now());
 END;
 $fn$ LANGUAGE plpgsql;
 
 CREATE FUNCTION bm25_refresh(mat regclass) RETURNS VOID AS $fn$
 DECLARE
-    tab regclass;
-    col NAME;
+    p bm_catalog.pg_bm25;
+    gen TEXT;
     upd_words INT;
     upd_docs INT;
     upd_dims INT;
 BEGIN
-    SELECT attrelid, attname INTO tab, col FROM bm_catalog.pg_bm25 WHERE matrelid = mat;
-    EXECUTE format('REFRESH MATERIALIZED VIEW %s', mat);
-    EXECUTE format('SELECT sum(how_many_tokens) FROM %s', mat) INTO upd_words;
-    EXECUTE format('SELECT count(%s) FROM %s', col, tab) INTO upd_docs;
-    EXECUTE format('SELECT count(*) FROM %s', mat) INTO upd_dims;
+    -- serializes refreshes, while readers keep using the current generation
+    SELECT * INTO p FROM bm_catalog.pg_bm25 WHERE matrelid = mat FOR UPDATE;
+    IF NOT FOUND THEN
+        RAISE EXCEPTION 'There is no BM25 statistics `%`.', mat;
+    END IF;
+    IF p.attrelid IS NULL THEN
+        RAISE EXCEPTION 'BM25 statistics `%` have no table to refresh from.', mat;
+    END IF;
+    -- next to the view, whose name may need quoting
+    SELECT format('%I.%I', n.nspname, c.relname || '_g' || (p.generation + 1)) INTO gen
+    FROM pg_catalog.pg_class c JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+    WHERE c.oid = mat;
+    PERFORM bm_catalog.bm25_build(gen, p.attrelid, p.attname, p.tokenizer, p.model, p.vocabulary, p.hash_bits, p.hash_seed, p.background, p.background_weight);
+    EXECUTE format('SELECT coalesce(sum(how_many_tokens), 0) FROM %s', gen) INTO upd_words;
+    EXECUTE format('SELECT count(%I) FROM %s', p.attname, p.attrelid) INTO upd_docs;
+    upd_docs := upd_docs + coalesce((SELECT round(p.background_weight * x.docs)::INT FROM bm_catalog.pg_bm25 x WHERE x.matrelid = p.background), 0);
+    upd_dims := p.dims;
+    IF p.vocabulary = 'corpus' THEN
+        EXECUTE format('SELECT count(*) FROM %s', gen) INTO upd_dims;
+    END IF;
+    -- the swap, visible to readers at commit
+    EXECUTE format('CREATE OR REPLACE VIEW %s AS SELECT * FROM %s', mat, gen);
     UPDATE bm_catalog.pg_bm25
-    SET words = upd_words, docs = upd_docs, dims = upd_dims
+    SET genrelid = gen::regclass, indexrelid = bm_catalog.bm25_index_of(gen::regclass), generation = p.generation + 1,
+        words = upd_words, docs = upd_docs, dims = upd_dims, refreshed_at = now(), baseline_changes = bm_catalog.bm25_table_changes(p.attrelid)
     WHERE matrelid = mat;
+    INSERT INTO bm_catalog.pg_bm25_generations (matrelid, generation, genrelid, indexrelid, words, docs, dims, b, k1, idf, created_at)
+    VALUES (mat, p.generation + 1, gen::regclass, bm_catalog.bm25_index_of(gen::regclass), upd_words, upd_docs, upd_dims, p.b, p.k1, p.idf, now());
+    UPDATE bm_catalog.pg_bm25_columns c SET pending = p.generation + 1, last_key = NULL
+    WHERE c.matrelid = mat AND c.keyname IS NOT NULL;
+    PERFORM bm_catalog.bm25_gc(mat);
+END;
+$fn$ LANGUAGE plpgsql;
+
+-- Drops generations that are not current, not the previous one, not pinned, and that no column of
+-- document vectors was embedded with. The previous generation serves readers that started before
+-- the last refresh.
+CREATE FUNCTION bm25_gc(mat regclass) RETURNS INT AS $fn$
+DECLARE
+    p_generation INT;
+    g RECORD;
+    dropped INT := 0;
+BEGIN
+    SELECT p.generation INTO p_generation FROM bm_catalog.pg_bm25 p WHERE p.matrelid = mat;
+    FOR g IN
+        SELECT * FROM bm_catalog.pg_bm25_generations x
+        WHERE x.matrelid = mat AND x.generation < p_generation - 1 AND NOT x.pinned
+        AND NOT EXISTS (SELECT 1 FROM bm_catalog.pg_bm25_columns c WHERE c.matrelid = mat AND c.generation = x.generation)
+    LOOP
+        EXECUTE format('DROP TABLE %s', g.genrelid);
+        DELETE FROM bm_catalog.pg_bm25_generations x WHERE x.matrelid = mat AND x.generation = g.generation;
+        dropped := dropped + 1;
+    END LOOP;
+    RETURN dropped;
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_pin(mat regclass, generation INT DEFAULT 0, pinned BOOLEAN DEFAULT true) RETURNS VOID AS $fn$
+BEGIN
+    UPDATE bm_catalog.pg_bm25_generations g SET pinned = bm25_pin.pinned
+    WHERE g.matrelid = mat AND g.generation = coalesce(nullif(bm25_pin.generation, 0), (SELECT p.generation FROM bm_catalog.pg_bm25 p WHERE p.matrelid = mat));
+    IF NOT FOUND THEN
+        RAISE EXCEPTION 'Generation % of `%` is not retained.', generation, mat;
+    END IF;
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_generations(mat regclass) RETURNS TABLE(generation INT, created_at TIMESTAMPTZ, docs INT, words INT, dims INT, b REAL, k1 REAL, idf TEXT, current BOOLEAN, pinned BOOLEAN, columns TEXT[]) STABLE STRICT AS $fn$
+    SELECT g.generation, g.created_at, g.docs, g.words, g.dims, g.b, g.k1, g.idf, g.generation = p.generation, g.pinned,
+        array(SELECT format('%s.%I', c.attrelid, c.attname) FROM bm_catalog.pg_bm25_columns c WHERE c.matrelid = mat AND c.generation = g.generation ORDER BY 1)
+    FROM bm_catalog.pg_bm25_generations g
+    JOIN bm_catalog.pg_bm25 p ON p.matrelid = g.matrelid
+    WHERE g.matrelid = mat
+    ORDER BY g.generation;
+$fn$ LANGUAGE SQL;
+
+-- Changes the scoring parameters in a new generation with the counts of the current one, so that
+-- vectors embedded with the current generation keep the parameters they were computed with.
+CREATE FUNCTION bm25_alter(mat regclass, b REAL DEFAULT NULL, k1 REAL DEFAULT NULL, idf TEXT DEFAULT NULL, reembed TEXT DEFAULT NULL, style TEXT DEFAULT bm_catalog.bm25_default('style')) RETURNS VOID AS $fn$
+DECLARE
+    p bm_catalog.pg_bm25;
+    gen TEXT;
+BEGIN
+    -- serializes with refreshes
+    SELECT * INTO p FROM bm_catalog.pg_bm25 x WHERE x.matrelid = mat FOR UPDATE;
+    IF NOT FOUND THEN
+        RAISE EXCEPTION 'There is no BM25 statistics `%`.', mat;
+    END IF;
+    IF b < 0 OR b > 1 OR k1 < 0 THEN
+        RAISE EXCEPTION 'BM25 parameters must satisfy 0 <= b <= 1 and k1 >= 0.';
+    END IF;
+    IF idf IS NOT NULL THEN
+        -- raises an error for an unknown variant
+        PERFORM bm_catalog.bm25_idf(idf, 1, 1);
+    END IF;
+    b := coalesce(b, p.b);
+    k1 := coalesce(k1, p.k1);
+    idf := coalesce(idf, p.idf);
+    IF (b, k1, idf) IS NOT DISTINCT FROM (p.b, p.k1, p.idf) THEN
+        RETURN;
+    END IF;
+    SELECT format('%I.%I', n.nspname, c.relname || '_g' || (p.generation + 1)) INTO gen
+    FROM pg_catalog.pg_class c JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+    WHERE c.oid = mat;
+    EXECUTE format('CREATE TABLE %s AS SELECT * FROM %s', gen, p.genrelid);
+    EXECUTE format('CREATE INDEX ON %s(%I)', gen, CASE WHEN p.vocabulary = 'corpus' THEN 'token' ELSE 'id' END);
+    EXECUTE format('CREATE OR REPLACE VIEW %s AS SELECT * FROM %s', mat, gen);
+    UPDATE bm_catalog.pg_bm25 x
+    SET genrelid = gen::regclass, indexrelid = bm_catalog.bm25_index_of(gen::regclass), generation = p.generation + 1,
+        b = bm25_alter.b, k1 = bm25_alter.k1, idf = bm25_alter.idf
+    WHERE x.matrelid = mat;
+    INSERT INTO bm_catalog.pg_bm25_generations (matrelid, generation, genrelid, indexrelid, words, docs, dims, b, k1, idf, created_at)
+    VALUES (mat, p.generation + 1, gen::regclass, bm_catalog.bm25_index_of(gen::regclass), p.words, p.docs, p.dims, b, k1, idf, now());
+    -- document vectors bake in b and k1, query vectors are computed with the new idf anyway
+    IF (b, k1) IS DISTINCT FROM (p.b, p.k1) THEN
+        UPDATE bm_catalog.pg_bm25_columns c SET pending = p.generation + 1, last_key = NULL
+        WHERE c.matrelid = mat AND c.keyname IS NOT NULL;
+        IF reembed IS NOT NULL THEN
+            PERFORM bm_catalog.bm25_reembed(mat, reembed, style);
+        ELSE
+            RAISE WARNING 'Document vectors computed from `%` before this change are stale, re-embed them with `bm25_reembed` or `bm25_reembed_attached`.', mat;
+        END IF;
+    ELSE
+        UPDATE bm_catalog.pg_bm25_columns c SET generation = p.generation + 1
+        WHERE c.matrelid = mat AND c.generation = p.generation;
+        UPDATE bm_catalog.pg_bm25_columns c SET pending = p.generation + 1
+        WHERE c.matrelid = mat AND c.pending = p.generation;
+    END IF;
+    PERFORM bm_catalog.bm25_gc(mat);
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_reembed(mat regclass, vector_col TEXT, style TEXT DEFAULT bm_catalog.bm25_default('style')) RETURNS BIGINT AS $fn$
+DECLARE
+    tab regclass;
+    col NAME;
+    vector_type TEXT;
+    updated BIGINT;
+BEGIN
+    SELECT attrelid, attname INTO tab, col FROM bm_catalog.pg_bm25 WHERE matrelid = mat;
+    IF tab IS NULL THEN
+        RAISE EXCEPTION 'BM25 statistics `%` have no table, attach columns with `bm25_attach` instead.', mat;
+    END IF;
+    SELECT format_type(atttypid, atttypmod) INTO vector_type FROM pg_catalog.pg_attribute WHERE attrelid = tab AND attname = vector_col AND NOT attisdropped;
+    IF vector_type IS NULL THEN
+        RAISE EXCEPTION 'There is no column `%` in `%`.', vector_col, tab;
+    END IF;
+    EXECUTE format('UPDATE %s SET %I = bm_catalog.bm25_document_to_svector(%L, %I, %L)::%s', tab, vector_col, mat, col, style, vector_type);
+    GET DIAGNOSTICS updated = ROW_COUNT;
+    UPDATE bm_catalog.pg_bm25 x SET baseline_changes = x.baseline_changes + updated WHERE x.attrelid = tab;
+    -- the column no longer holds the generation it was embedded with before
+    INSERT INTO bm_catalog.pg_bm25_columns (matrelid, attrelid, attname, source, style, attnum, sourcenum, generation, embedded_at)
+    SELECT mat, tab, vector_col, col, style, bm_catalog.bm25_attnum(tab, vector_col), p.attnum, p.generation, now() FROM bm_catalog.pg_bm25 p WHERE p.matrelid = mat
+    ON CONFLICT (attrelid, attname) DO UPDATE
+    SET matrelid = excluded.matrelid, generation = excluded.generation, embedded_at = excluded.embedded_at, pending = NULL, last_key = NULL;
+    PERFORM bm_catalog.bm25_gc(mat);
+    RETURN updated;
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_attach(mat regclass, tab regclass, source_col TEXT, vector_col TEXT, style TEXT DEFAULT bm_catalog.bm25_default('style'), key_col TEXT DEFAULT 'id') RETURNS VOID AS $fn$
+DECLARE
+    col TEXT;
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM bm_catalog.pg_bm25 p WHERE p.matrelid = mat) THEN
+        RAISE EXCEPTION 'There is no BM25 statistics `%`.', mat;
+    END IF;
+    FOREACH col IN ARRAY ARRAY[source_col, vector_col, key_col] LOOP
+        IF NOT EXISTS (SELECT 1 FROM pg_catalog.pg_attribute a WHERE a.attrelid = tab AND a.attname = col AND a.attnum > 0 AND NOT a.attisdropped) THEN
+            RAISE EXCEPTION 'There is no column `%` in `%`.', col, tab;
+        END IF;
+    END LOOP;
+    INSERT INTO bm_catalog.pg_bm25_columns (matrelid, attrelid, attname, source, style, keyname, attnum, sourcenum, keynum, pending)
+    SELECT mat, tab, vector_col, source_col, style, key_col, bm_catalog.bm25_attnum(tab, vector_col), bm_catalog.bm25_attnum(tab, source_col), bm_catalog.bm25_attnum(tab, key_col), p.generation FROM bm_catalog.pg_bm25 p WHERE p.matrelid = mat;
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_detach(mat regclass, tab regclass, vector_col TEXT) RETURNS VOID AS $fn$
+BEGIN
+    DELETE FROM bm_catalog.pg_bm25_columns c WHERE c.matrelid = mat AND c.attrelid = tab AND c.attname = vector_col;
+    IF NOT FOUND THEN
+        RAISE EXCEPTION 'Column `%` of `%` is not attached to `%`.', vector_col, tab, mat;
+    END IF;
+    PERFORM bm_catalog.bm25_gc(mat);
+END;
+$fn$ LANGUAGE plpgsql;
+
+-- Re-embeds the next batch of rows of each pending attached column, and returns the number of
+-- updated rows. A column is done once a batch is not full.
+CREATE FUNCTION bm25_reembed_batch(mat regclass, batch_size INT DEFAULT 1000) RETURNS BIGINT AS $fn$
+DECLARE
+    c bm_catalog.pg_bm25_columns;
+    vector_type TEXT;
+    key_type TEXT;
+    updated BIGINT;
+    max_key TEXT;
+    total BIGINT := 0;
+BEGIN
+    IF batch_size < 1 THEN
+        RAISE EXCEPTION 'Batch size must be positive.';
+    END IF;
+    FOR c IN SELECT * FROM bm_catalog.pg_bm25_columns x WHERE x.matrelid = mat AND x.pending IS NOT NULL AND x.keyname IS NOT NULL FOR UPDATE LOOP
+        SELECT format_type(a.atttypid, a.atttypmod) INTO vector_type FROM pg_catalog.pg_attribute a WHERE a.attrelid = c.attrelid AND a.attname = c.attname;
+        SELECT format_type(a.atttypid, a.atttypmod) INTO key_type FROM pg_catalog.pg_attribute a WHERE a.attrelid = c.attrelid AND a.attname = c.keyname;
+        EXECUTE format($$
+            WITH
+                batch AS (SELECT %1$I AS k FROM %2$s WHERE $1 IS NULL OR %1$I > $1::%3$s ORDER BY %1$I LIMIT $2),
+                done AS (
+                    UPDATE %2$s t SET %4$I = bm_catalog.bm25_document_to_svector($3, t.%5$I, $4, $5)::%6$s
+                    FROM batch WHERE t.%1$I = batch.k
+                    RETURNING t.%1$I AS k
+                )
+            SELECT count(*), max(k)::TEXT FROM done
+        $$, c.keyname, c.attrelid, key_type, c.attname, c.source, vector_type)
+        INTO updated, max_key
+        USING c.last_key, batch_size, mat, c.style, c.pending;
+        total := total + updated;
+        UPDATE bm_catalog.pg_bm25 x SET baseline_changes = x.baseline_changes + updated WHERE x.attrelid = c.attrelid;
+        IF updated < batch_size THEN
+            UPDATE bm_catalog.pg_bm25_columns x SET generation = c.pending, embedded_at = now(), pending = NULL, last_key = NULL
+            WHERE x.attrelid = c.attrelid AND x.attname = c.attname;
+        ELSE
+            UPDATE bm_catalog.pg_bm25_columns x SET last_key = max_key
+            WHERE x.attrelid = c.attrelid AND x.attname = c.attname;
+        END IF;
+    END LOOP;
+    PERFORM bm_catalog.bm25_gc(mat);
+    RETURN total;
+END;
+$fn$ LANGUAGE plpgsql;
+
+-- Re-embeds pending attached columns, committing after each batch so that an interrupted run
+-- resumes where it stopped.
+CREATE PROCEDURE bm25_reembed_attached(mat regclass, batch_size INT DEFAULT 1000) AS $fn$
+BEGIN
+    WHILE bm_catalog.bm25_reembed_batch(mat, batch_size) > 0 LOOP
+        COMMIT;
+    END LOOP;
+END;
+$fn$ LANGUAGE plpgsql;
+
+-- One round of maintenance, run by the background worker: refreshes the statistics whose table
+-- changed by at least `threshold` times its number of documents since the last refresh, and
+-- re-embeds a batch of each pending attached column. Returns the number of refreshed statistics.
+CREATE FUNCTION bm25_maintain(threshold REAL DEFAULT 0.1, batch_size INT DEFAULT 1000) RETURNS INT AS $fn$
+DECLARE
+    s RECORD;
+    message TEXT;
+    refreshed INT := 0;
+BEGIN
+    -- counts restarted by a reset of the statistics collector or a crash
+    UPDATE bm_catalog.pg_bm25 p SET baseline_changes = 0
+    WHERE p.baseline_changes > coalesce(bm_catalog.bm25_table_changes(p.attrelid), 0);
+    FOR s IN SELECT * FROM bm_catalog.pg_bm25_status LOOP
+        BEGIN
+            IF threshold > 0 AND s.changes >= threshold * greatest(s.docs, 1) THEN
+                PERFORM bm_catalog.bm25_refresh(s.matrelid);
+                refreshed := refreshed + 1;
+            END IF;
+            PERFORM bm_catalog.bm25_reembed_batch(s.matrelid, batch_size);
+            UPDATE bm_catalog.pg_bm25 p SET maintained_at = now(), maintenance_error = NULL WHERE p.matrelid = s.matrelid;
+        EXCEPTION WHEN OTHERS THEN
+            GET STACKED DIAGNOSTICS message = MESSAGE_TEXT;
+            RAISE WARNING 'Maintenance of `%` failed: %', s.matrelid, message;
+            UPDATE bm_catalog.pg_bm25 p SET maintained_at = now(), maintenance_error = message WHERE p.matrelid = s.matrelid;
+        END;
+    END LOOP;
+    RETURN refreshed;
 END;
 $fn$ LANGUAGE plpgsql;
 
 CREATE FUNCTION bm25_drop(mat regclass) RETURNS VOID AS $fn$
+DECLARE
+    gens regclass[];
+    gen regclass;
 BEGIN
-    EXECUTE format('DROP MATERIALIZED VIEW %s', mat);
+    -- the catalog goes first, so that `bm25_sql_drop` finds nothing to clean up
+    SELECT array_agg(g.genrelid) INTO gens FROM bm_catalog.pg_bm25_generations g WHERE g.matrelid = mat;
+    DELETE FROM bm_catalog.pg_bm25_generations
+    WHERE matrelid = mat;
+    DELETE FROM bm_catalog.pg_bm25_columns
+    WHERE matrelid = mat;
     DELETE FROM bm_catalog.pg_bm25
     WHERE matrelid = mat;
+    EXECUTE format('DROP VIEW %s', mat);
+    FOREACH gen IN ARRAY coalesce(gens, '{}') LOOP
+        EXECUTE format('DROP TABLE %s', gen);
+    END LOOP;
 END;
 $fn$ LANGUAGE plpgsql;
 
-CREATE FUNCTION bm25_document_to_svector(mat regclass, t TEXT, style TEXT DEFAULT 'pgvecto.rs') RETURNS text STABLE STRICT PARALLEL SAFE AS $fn$
+CREATE FUNCTION bm25_vocabulary(mat regclass) RETURNS TABLE(token TEXT, id INT, df INT, cf INT, idf REAL) STABLE STRICT AS $fn$
 DECLARE
+    p_vocabulary TEXT;
+    p_idf TEXT;
+    p_docs INT;
+BEGIN
+    SELECT p.vocabulary, p.idf, p.docs INTO p_vocabulary, p_idf, p_docs FROM bm_catalog.pg_bm25 p WHERE p.matrelid = mat;
+    IF NOT FOUND THEN
+        RAISE EXCEPTION 'There is no BM25 statistics `%`.', mat;
+    END IF;
+    IF p_vocabulary = 'corpus' THEN
+        RETURN QUERY EXECUTE format('SELECT token::TEXT, id, token_in_how_many_inputs, how_many_tokens, bm_catalog.bm25_idf($1, $2, token_in_how_many_inputs) FROM %s ORDER BY id', mat) USING p_idf, p_docs;
+    ELSE
+        RETURN QUERY EXECUTE format('SELECT NULL::TEXT, id, token_in_how_many_inputs, how_many_tokens, bm_catalog.bm25_idf($1, $2, token_in_how_many_inputs) FROM %s ORDER BY id', mat) USING p_idf, p_docs;
+    END IF;
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_top_terms(mat regclass, n INT DEFAULT 10) RETURNS TABLE(token TEXT, id INT, df INT, cf INT, idf REAL) STABLE STRICT AS $fn$
+    SELECT * FROM bm_catalog.bm25_vocabulary(mat) v ORDER BY v.df DESC, v.cf DESC, v.id LIMIT n;
+$fn$ LANGUAGE SQL;
+
+-- Entries of the tokens of `t`, one row per token in order of first occurrence. Tokens outside the
+-- vocabulary have no counts.
+CREATE FUNCTION bm25_term_info(mat regclass, t TEXT) RETURNS TABLE(token TEXT, id INT, df INT, cf INT, idf REAL) STABLE STRICT AS $fn$
+DECLARE
+    p_vocabulary TEXT;
+    p_hash_bits INT;
+    p_hash_seed INT;
+    p_tokenizer TEXT;
+    p_model TEXT;
+    p_idf TEXT;
+    p_docs INT;
+    tokens TEXT[];
+    ids INT[];
+BEGIN
+    SELECT p.vocabulary, p.hash_bits, p.hash_seed, p.tokenizer, p.model, p.idf, p.docs INTO p_vocabulary, p_hash_bits, p_hash_seed, p_tokenizer, p_model, p_idf, p_docs FROM bm_catalog.pg_bm25 p WHERE p.matrelid = mat;
+    IF NOT FOUND THEN
+        RAISE EXCEPTION 'There is no BM25 statistics `%`.', mat;
+    END IF;
+    tokens := bm_catalog.tokenize(t, p_tokenizer, p_model);
+    IF p_vocabulary = 'corpus' THEN
+        RETURN QUERY EXECUTE format($$
+            SELECT x.token, g.id, g.token_in_how_many_inputs, g.how_many_tokens, bm_catalog.bm25_idf($2, $3, g.token_in_how_many_inputs)
+            FROM (SELECT u.token, min(u.n) AS n FROM unnest($1) WITH ORDINALITY u(token, n) GROUP BY u.token) x
+            LEFT JOIN %s g ON g.token = x.token::NAME
+            ORDER BY x.n
+        $$, mat) USING tokens, p_idf, p_docs;
+        RETURN;
+    ELSIF p_vocabulary = 'fixed' THEN
+        ids := bm_catalog.tokenize_ids(t, p_tokenizer, p_model);
+    ELSE
+        -- buckets of the tokens, in the same order
+        ids := bm_catalog.tokenize_hashed(t, p_tokenizer, p_model, p_hash_bits, p_hash_seed);
+    END IF;
+    RETURN QUERY EXECUTE format($$
+        SELECT x.token, x.id, g.token_in_how_many_inputs, g.how_many_tokens, bm_catalog.bm25_idf($3, $4, g.token_in_how_many_inputs)
+        FROM (SELECT u.token, u.id, min(u.n) AS n FROM unnest($1, $2) WITH ORDINALITY u(token, id, n) GROUP BY u.token, u.id) x
+        LEFT JOIN %s g ON g.id = x.id
+        ORDER BY x.n
+    $$, mat) USING tokens, ids, p_idf, p_docs;
+END;
+$fn$ LANGUAGE plpgsql;
+
+-- SHA-256 of an export: a line of its header fields, and lines `id<TAB>token<TAB>df<TAB>cf` of the
+-- terms in order of id. Fields are separated by tabs, and missing fields and the token of `fixed`
+-- and `hashed` statistics are empty.
+CREATE FUNCTION bm25_checksum(data jsonb) RETURNS TEXT IMMUTABLE STRICT AS $fn$
+    SELECT encode(sha256(convert_to(
+        (SELECT string_agg(coalesce(data->>h.key, ''), E'\t' ORDER BY h.n)
+         FROM unnest(ARRAY['format', 'version', 'tokenizer', 'model', 'vocabulary', 'hash_bits', 'hash_seed', 'b', 'k1', 'idf', 'docs', 'words', 'dims', 'generation']) WITH ORDINALITY h(key, n))
+        || E'\n' || coalesce(string_agg(
+            concat_ws(E'\t', t->>'id', coalesce(t->>'token', ''), t->>'df', t->>'cf') || E'\n', '' ORDER BY (t->>'id')::INT
+        ), ''), 'UTF8')), 'hex')
+    FROM jsonb_array_elements(data->'terms') t;
+$fn$ LANGUAGE SQL;
+
+-- The current generation of statistics as a document of format `pg_bestmatch`, which can be
+-- imported with `bm25_import`.
+CREATE FUNCTION bm25_export(mat regclass, format TEXT DEFAULT 'pg_bestmatch') RETURNS jsonb STABLE STRICT AS $fn$
+DECLARE
+    p bm_catalog.pg_bm25;
+    terms jsonb;
+    data jsonb;
+BEGIN
+    SELECT * INTO p FROM bm_catalog.pg_bm25 x WHERE x.matrelid = mat;
+    IF NOT FOUND THEN
+        RAISE EXCEPTION 'There is no BM25 statistics `%`.', mat;
+    END IF;
+    IF bm25_export.format = 'pg_bestmatch' THEN
+        SELECT coalesce(jsonb_agg(jsonb_build_object('id', v.id, 'token', v.token, 'df', v.df, 'cf', v.cf, 'idf', v.idf) ORDER BY v.id), '[]')
+        INTO terms FROM bm_catalog.bm25_vocabulary(mat) v;
+        data := jsonb_build_object(
+            'format', 'pg_bestmatch', 'version', 1,
+            'tokenizer', p.tokenizer, 'model', p.model, 'vocabulary', p.vocabulary, 'hash_bits', p.hash_bits, 'hash_seed', p.hash_seed,
+            'b', p.b, 'k1', p.k1, 'idf', p.idf, 'docs', p.docs, 'words', p.words, 'dims', p.dims, 'generation', p.generation,
+            'terms', terms
+        );
+        RETURN data || jsonb_build_object('checksum', bm_catalog.bm25_checksum(data));
+    ELSE
+        RAISE EXCEPTION 'Unknown format `%`.', bm25_export.format;
+    END IF;
+END;
+$fn$ LANGUAGE plpgsql;
+
+-- Creates statistics `mat` from an export of format `pg_bestmatch`, with the vocabulary of the
+-- export as its first generation. Later refreshes rebuild it from `col` of `tab`, and statistics
+-- without a table are never refreshed.
+CREATE FUNCTION bm25_import(mat TEXT, data jsonb, tab regclass DEFAULT NULL, col TEXT DEFAULT NULL) RETURNS VOID AS $fn$
+DECLARE
+    gen TEXT;
+    p_vocabulary TEXT := data->>'vocabulary';
+    p_docs INT := (data->>'docs')::INT;
+    p_words INT := (data->>'words')::INT;
+    p_dims INT := (data->>'dims')::INT;
+    p_hash_bits INT := (data->>'hash_bits')::INT;
+    tokens NAME[];
+    ids INT[];
+    cfs INT[];
+    dfs INT[];
+BEGIN
+    IF data->>'format' IS DISTINCT FROM 'pg_bestmatch' OR data->>'version' IS DISTINCT FROM '1' THEN
+        RAISE EXCEPTION 'Unsupported export: format `%`, version `%`.', data->>'format', data->>'version';
+    END IF;
+    IF bm_catalog.bm25_checksum(data) IS DISTINCT FROM data->>'checksum' THEN
+        RAISE EXCEPTION 'The checksum of the export does not match its contents.';
+    END IF;
+    IF (tab IS NOT NULL OR col IS NOT NULL) AND NOT EXISTS (SELECT 1 FROM pg_catalog.pg_attribute WHERE attrelid = tab AND attname = col AND atttypid = 'text'::regtype) THEN
+        RAISE EXCEPTION 'This is no such table or no such column or column is not of type `text`.';
+    END IF;
+    IF (data->>'b')::REAL NOT BETWEEN 0 AND 1 OR (data->>'k1')::REAL < 0 THEN
+        RAISE EXCEPTION 'BM25 parameters must satisfy 0 <= b <= 1 and k1 >= 0.';
+    END IF;
+    PERFORM bm_catalog.bm25_idf(data->>'idf', 1, 1);
+    IF p_vocabulary NOT IN ('corpus', 'fixed', 'hashed') THEN
+        RAISE EXCEPTION 'Unknown vocabulary `%`.', p_vocabulary;
+    END IF;
+    IF p_vocabulary = 'fixed' AND bm_catalog.has_bigrams(data->>'model') THEN
+        RAISE EXCEPTION 'Bigrams have no native token ids, use vocabulary `corpus` or `hashed` with the `bigrams` option.';
+    END IF;
+    IF p_vocabulary = 'hashed' AND (p_hash_bits IS NULL OR p_hash_bits NOT BETWEEN 1 AND 30) THEN
+        RAISE EXCEPTION 'Hash bits must be between 1 and 30.';
+    END IF;
+    SELECT coalesce(array_agg(t->>'token' ORDER BY (t->>'id')::INT), '{}')::NAME[], coalesce(array_agg((t->>'id')::INT ORDER BY (t->>'id')::INT), '{}'),
+        coalesce(array_agg((t->>'cf')::INT ORDER BY (t->>'id')::INT), '{}'), coalesce(array_agg((t->>'df')::INT ORDER BY (t->>'id')::INT), '{}')
+    INTO tokens, ids, cfs, dfs
+    FROM jsonb_array_elements(data->'terms') t;
+    -- vectors of the statistics have `dims` dimensions, numbered by the ids of the terms
+    IF p_dims IS NULL OR p_dims < coalesce(ids[array_upper(ids, 1)] + 1, 0) OR ids[1] < 0
+        OR p_vocabulary = 'hashed' AND p_dims <> 1 << p_hash_bits THEN
+        RAISE EXCEPTION 'The ids of the terms of the export are not dimensions of its `dims`.';
+    END IF;
+    IF (SELECT count(DISTINCT id) FROM unnest(ids) id) <> cardinality(ids)
+        OR p_vocabulary = 'corpus' AND (SELECT count(DISTINCT token) FROM unnest(tokens) token) <> cardinality(tokens) THEN
+        RAISE EXCEPTION 'The terms of the export are not unique.';
+    END IF;
+    gen := bm_catalog.bm25_name(mat, '_g1');
+    IF p_vocabulary = 'corpus' THEN
+        EXECUTE format($$
+            CREATE TABLE %s AS
+                SELECT token COLLATE "C" AS token, id, how_many_tokens, token_in_how_many_inputs
+                FROM unnest($1, $2, $3, $4) AS t(token, id, how_many_tokens, token_in_how_many_inputs)
+                ORDER BY id
+        $$, gen) USING tokens, ids, cfs, dfs;
+        EXECUTE format('CREATE INDEX ON %s(token)', gen);
+    ELSE
+        EXECUTE format($$
+            CREATE TABLE %s AS
+                SELECT id, how_many_tokens, token_in_how_many_inputs
+                FROM unnest($1, $2, $3) AS t(id, how_many_tokens, token_in_how_many_inputs)
+                ORDER BY id
+        $$, gen) USING ids, cfs, dfs;
+        EXECUTE format('CREATE INDEX ON %s(id)', gen);
+    END IF;
+    EXECUTE format('CREATE VIEW %s AS SELECT * FROM %s', bm_catalog.bm25_name(mat), gen);
+    INSERT INTO bm_catalog.pg_bm25 (attrelid, attname, attnum, matrelid, genrelid, indexrelid, b, k1, idf, tokenizer, model, vocabulary, hash_bits, hash_seed, words, docs, dims, generation, refreshed_at, baseline_changes)
+    VALUES (tab, col, bm_catalog.bm25_attnum(tab, col), bm_catalog.bm25_name(mat)::regclass, gen::regclass, bm_catalog.bm25_index_of(gen::regclass), (data->>'b')::REAL, (data->>'k1')::REAL, data->>'idf',
+        data->>'tokenizer', data->>'model', p_vocabulary, (data->>'hash_bits')::INT, (data->>'hash_seed')::INT, p_words, p_docs, (data->>'dims')::INT, 1, now(), coalesce(bm_catalog.bm25_table_changes(tab), 0));
+    INSERT INTO bm_catalog.pg_bm25_generations (matrelid, generation, genrelid, indexrelid, words, docs, dims, b, k1, idf, created_at)
+    VALUES (bm_catalog.bm25_name(mat)::regclass, 1, gen::regclass, bm_catalog.bm25_index_of(gen::regclass), p_words, p_docs, (data->>'dims')::INT, (data->>'b')::REAL, (data->>'k1')::REAL, data->>'idf', now());
+END;
+$fn$ LANGUAGE plpgsql;
+
+-- Creates statistics `mat` without a table from the document frequencies of a background corpus,
+-- the columns `token`, `df` and optionally `cf` of `counts`. Counts of tokens listed twice add up.
+-- `hashed` statistics hash the tokens into buckets, and `fixed` statistics take the token ids of
+-- the tokenizer from a column `id` instead of `token`.
+CREATE FUNCTION bm25_import_counts(mat TEXT, counts regclass, docs INT, words INT DEFAULT NULL, tokenizer TEXT DEFAULT bm_catalog.bm25_default('tokenizer'), model TEXT DEFAULT bm_catalog.bm25_default('model'), b REAL DEFAULT bm_catalog.bm25_default('b')::REAL, k1 REAL DEFAULT bm_catalog.bm25_default('k1')::REAL, idf TEXT DEFAULT 'bm25', vocabulary TEXT DEFAULT 'corpus', hash_bits INT DEFAULT 20, hash_seed INT DEFAULT 0) RETURNS VOID AS $fn$
+DECLARE
+    gen TEXT;
+    key TEXT := 'token';
+    cf TEXT := 'df';
+    ins_words INT;
+    ins_dims INT;
+    bad_id INT;
+BEGIN
+    IF vocabulary = 'corpus' THEN
+        hash_bits := NULL;
+        hash_seed := NULL;
+    ELSIF vocabulary = 'fixed' THEN
+        IF bm_catalog.has_bigrams(model) THEN
+            RAISE EXCEPTION 'Bigrams have no native token ids, use vocabulary `corpus` or `hashed` with the `bigrams` option.';
+        END IF;
+        key := 'id';
+        ins_dims := bm_catalog.vocab_size(tokenizer, model);
+        hash_bits := NULL;
+        hash_seed := NULL;
+    ELSIF vocabulary = 'hashed' THEN
+        IF hash_bits IS NULL OR hash_bits NOT BETWEEN 1 AND 30 THEN
+            RAISE EXCEPTION 'Hash bits must be between 1 and 30.';
+        END IF;
+        ins_dims := 1 << hash_bits;
+    ELSE
+        RAISE EXCEPTION 'Unknown vocabulary `%`.', vocabulary;
+    END IF;
+    IF NOT EXISTS (SELECT 1 FROM pg_catalog.pg_attribute a WHERE a.attrelid = counts AND a.attname = key AND NOT a.attisdropped)
+        OR NOT EXISTS (SELECT 1 FROM pg_catalog.pg_attribute a WHERE a.attrelid = counts AND a.attname = 'df' AND NOT a.attisdropped) THEN
+        RAISE EXCEPTION 'There are no columns `%` and `df` in `%`.', key, counts;
+    END IF;
+    -- without collection frequencies, each document has a token once
+    IF EXISTS (SELECT 1 FROM pg_catalog.pg_attribute a WHERE a.attrelid = counts AND a.attname = 'cf' AND NOT a.attisdropped) THEN
+        cf := 'cf';
+    END IF;
+    IF docs IS NULL OR docs < 1 THEN
+        RAISE EXCEPTION 'The background corpus must have at least one document.';
+    END IF;
+    IF b < 0 OR b > 1 OR k1 < 0 THEN
+        RAISE EXCEPTION 'BM25 parameters must satisfy 0 <= b <= 1 and k1 >= 0.';
+    END IF;
+    PERFORM bm_catalog.bm25_idf(idf, 1, 1);
+    IF vocabulary = 'fixed' THEN
+        EXECUTE format('SELECT id FROM %s WHERE id < 0 OR id >= %s LIMIT 1', counts, ins_dims) INTO bad_id;
+        IF bad_id IS NOT NULL THEN
+            RAISE EXCEPTION 'Token id % of `%` is not between 0 and %.', bad_id, counts, ins_dims - 1;
+        END IF;
+    END IF;
+    gen := bm_catalog.bm25_name(mat, '_g1');
+    IF vocabulary = 'corpus' THEN
+        EXECUTE format($$
+            CREATE TABLE %s AS
+                SELECT
+                    token,
+                    (row_number() OVER (ORDER BY token) - 1)::INT AS id,
+                    sum(how_many_tokens)::INT AS how_many_tokens,
+                    sum(token_in_how_many_inputs)::INT AS token_in_how_many_inputs
+                FROM (SELECT token::NAME COLLATE "C" AS token, %I AS how_many_tokens, df AS token_in_how_many_inputs FROM %s WHERE token IS NOT NULL AND df > 0) t
+                GROUP BY token
+                ORDER BY token;
+            CREATE INDEX ON %s(token);
+        $$, gen, cf, counts, gen);
+        EXECUTE format('SELECT count(*) FROM %s', gen) INTO ins_dims;
+    ELSE
+        -- buckets of `hashed` statistics are those of `tokenize_hashed`
+        EXECUTE format($$
+            CREATE TABLE %s AS
+                SELECT
+                    id,
+                    sum(how_many_tokens)::INT AS how_many_tokens,
+                    sum(token_in_how_many_inputs)::INT AS token_in_how_many_inputs
+                FROM (SELECT %s AS id, %I AS how_many_tokens, df AS token_in_how_many_inputs FROM %s WHERE %I IS NOT NULL AND df > 0) t
+                GROUP BY id
+                ORDER BY id;
+            CREATE INDEX ON %s(id);
+        $$, gen,
+            CASE WHEN vocabulary = 'fixed' THEN 'id' ELSE format('(bm_catalog.murmur3_32(token, %s) & %s)::INT', hash_seed, ins_dims - 1) END,
+            cf, counts, key, gen);
+    END IF;
+    EXECUTE format('SELECT coalesce(sum(how_many_tokens), 0) FROM %s', gen) INTO ins_words;
+    EXECUTE format('CREATE VIEW %s AS SELECT * FROM %s', bm_catalog.bm25_name(mat), gen);
+    INSERT INTO bm_catalog.pg_bm25 (matrelid, genrelid, indexrelid, b, k1, idf, tokenizer, model, vocabulary, hash_bits, hash_seed, words, docs, dims, generation, refreshed_at)
+    VALUES (bm_catalog.bm25_name(mat)::regclass, gen::regclass, bm_catalog.bm25_index_of(gen::regclass), b, k1, idf, tokenizer, model, vocabulary, hash_bits, hash_seed, coalesce(words, ins_words, 0), docs, ins_dims, 1, now());
+    INSERT INTO bm_catalog.pg_bm25_generations (matrelid, generation, genrelid, indexrelid, words, docs, dims, b, k1, idf, created_at)
+    VALUES (bm_catalog.bm25_name(mat)::regclass, 1, gen::regclass, bm_catalog.bm25_index_of(gen::regclass), coalesce(words, ins_words, 0), docs, ins_dims, b, k1, idf, now());
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_stats(mat regclass) RETURNS TABLE(docs INT, words INT, avgdl REAL, vocabulary_size INT, dims INT, vocabulary TEXT, tokenizer TEXT, model TEXT, b REAL, k1 REAL, idf TEXT, refreshed_at TIMESTAMPTZ, size BIGINT) STABLE STRICT AS $fn$
+DECLARE
+    p_vocabulary_size INT;
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM bm_catalog.pg_bm25 p WHERE p.matrelid = mat) THEN
+        RAISE EXCEPTION 'There is no BM25 statistics `%`.', mat;
+    END IF;
+    EXECUTE format('SELECT count(*) FROM %s', mat) INTO p_vocabulary_size;
+    RETURN QUERY
+    SELECT p.docs, p.words, (p.words::REAL / nullif(p.docs, 0))::REAL, p_vocabulary_size, p.dims, p.vocabulary, p.tokenizer, p.model, p.b, p.k1, p.idf, p.refreshed_at, pg_catalog.pg_total_relation_size(p.genrelid)
+    FROM bm_catalog.pg_bm25 p WHERE p.matrelid = mat;
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_document_to_svector(mat regclass, t TEXT, style TEXT DEFAULT bm_catalog.bm25_default('style'), generation INT DEFAULT 0) RETURNS text STABLE STRICT PARALLEL SAFE AS $fn$
+DECLARE
+    gen regclass;
     idx regclass;
     p_b REAL;
     p_k1 REAL;
     p_words INT;
     p_docs INT;
     p_dims INT;
+    p_vocabulary TEXT;
+    p_hash_bits INT;
+    p_hash_seed INT;
+    p_tokenizer TEXT;
+    p_model TEXT;
+BEGIN
+    SELECT genrelid, indexrelid, b, k1, words, docs, dims, vocabulary, coalesce(hash_bits, 0), coalesce(hash_seed, 0), tokenizer, model INTO gen, idx, p_b, p_k1, p_words, p_docs, p_dims, p_vocabulary, p_hash_bits, p_hash_seed, p_tokenizer, p_model FROM bm_catalog.pg_bm25 WHERE matrelid = mat;
+    -- an earlier generation scores consistently with document vectors embedded with it
+    IF generation != 0 THEN
+        SELECT g.genrelid, g.indexrelid, g.b, g.k1, g.words, g.docs, g.dims INTO gen, idx, p_b, p_k1, p_words, p_docs, p_dims FROM bm_catalog.pg_bm25_generations g WHERE g.matrelid = mat AND g.generation = bm25_document_to_svector.generation;
+        IF NOT FOUND THEN
+            RAISE EXCEPTION 'Generation % of `%` is not retained.', generation, mat;
+        END IF;
+    END IF;
+    RETURN bm_catalog.bm25_document_to_svector_internal(gen::oid, idx::oid, p_b, p_k1, p_words, p_docs, p_dims, t, style, p_vocabulary, p_hash_bits, p_hash_seed, p_tokenizer, p_model);
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_query_to_svector(mat regclass, t TEXT, style TEXT DEFAULT bm_catalog.bm25_default('style'), generation INT DEFAULT 0) RETURNS text STABLE STRICT PARALLEL SAFE AS $fn$
+DECLARE
+    gen regclass;
+    idx regclass;
+    p_b REAL;
+    p_k1 REAL;
+    p_words INT;
+    p_docs INT;
+    p_dims INT;
+    p_idf TEXT;
+    p_vocabulary TEXT;
+    p_hash_bits INT;
+    p_hash_seed INT;
+    p_tokenizer TEXT;
+    p_model TEXT;
+BEGIN
+    SELECT genrelid, indexrelid, b, k1, words, docs, dims, idf, vocabulary, coalesce(hash_bits, 0), coalesce(hash_seed, 0), tokenizer, model INTO gen, idx, p_b, p_k1, p_words, p_docs, p_dims, p_idf, p_vocabulary, p_hash_bits, p_hash_seed, p_tokenizer, p_model FROM bm_catalog.pg_bm25 WHERE matrelid = mat;
+    IF generation != 0 THEN
+        SELECT g.genrelid, g.indexrelid, g.b, g.k1, g.words, g.docs, g.dims, g.idf INTO gen, idx, p_b, p_k1, p_words, p_docs, p_dims, p_idf FROM bm_catalog.pg_bm25_generations g WHERE g.matrelid = mat AND g.generation = bm25_query_to_svector.generation;
+        IF NOT FOUND THEN
+            RAISE EXCEPTION 'Generation % of `%` is not retained.', generation, mat;
+        END IF;
+    END IF;
+    RETURN bm_catalog.bm25_query_to_svector_internal(gen::oid, idx::oid, p_b, p_k1, p_words, p_docs, p_dims, p_idf, t, style, p_vocabulary, p_hash_bits, p_hash_seed, p_tokenizer, p_model);
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_explain(mat regclass, document TEXT, query TEXT, style TEXT DEFAULT bm_catalog.bm25_default('style'), generation INT DEFAULT 0) RETURNS TABLE(token TEXT, id INT, query_weight REAL, tf INT, idf REAL, norm REAL, document_weight REAL, contribution REAL) STABLE STRICT PARALLEL SAFE AS $fn$
+DECLARE
+    gen regclass;
+    idx regclass;
+    p_b REAL;
+    p_k1 REAL;
+    p_words INT;
+    p_docs INT;
+    p_idf TEXT;
+    p_vocabulary TEXT;
+    p_hash_bits INT;
+    p_hash_seed INT;
     p_tokenizer TEXT;
     p_model TEXT;
 BEGIN
-    SELECT indexrelid, b, k1, words, docs, dims, tokenizer, model INTO idx, p_b, p_k1, p_words, p_docs, p_dims, p_tokenizer, p_model FROM bm_catalog.pg_bm25 WHERE matrelid = mat;
-    RETURN bm_catalog.bm25_document_to_svector_internal(mat::oid, idx::oid, p_b, p_k1, p_words, p_docs, p_dims, t, style, p_tokenizer, p_model);
+    SELECT p.genrelid, p.indexrelid, p.b, p.k1, p.words, p.docs, p.idf, p.vocabulary, coalesce(p.hash_bits, 0), coalesce(p.hash_seed, 0), p.tokenizer, p.model INTO gen, idx, p_b, p_k1, p_words, p_docs, p_idf, p_vocabulary, p_hash_bits, p_hash_seed, p_tokenizer, p_model FROM bm_catalog.pg_bm25 p WHERE p.matrelid = mat;
+    IF generation != 0 THEN
+        SELECT g.genrelid, g.indexrelid, g.b, g.k1, g.words, g.docs, g.idf INTO gen, idx, p_b, p_k1, p_words, p_docs, p_idf FROM bm_catalog.pg_bm25_generations g WHERE g.matrelid = mat AND g.generation = bm25_explain.generation;
+        IF NOT FOUND THEN
+            RAISE EXCEPTION 'Generation % of `%` is not retained.', generation, mat;
+        END IF;
+    END IF;
+    RETURN QUERY SELECT * FROM bm_catalog.bm25_explain_internal(gen::oid, idx::oid, p_b, p_k1, p_words, p_docs, p_idf, document, query, style, p_vocabulary, p_hash_bits, p_hash_seed, p_tokenizer, p_model);
 END;
 $fn$ LANGUAGE plpgsql;
 
-CREATE FUNCTION bm25_query_to_svector(mat regclass, t TEXT, style TEXT DEFAULT 'pgvecto.rs') RETURNS text STABLE STRICT PARALLEL SAFE AS $fn$
+CREATE FUNCTION bm25_structured_query_to_svector(mat regclass, t TEXT, style TEXT DEFAULT bm_catalog.bm25_default('style'), generation INT DEFAULT 0) RETURNS text STABLE STRICT PARALLEL SAFE AS $fn$
 DECLARE
+    gen regclass;
     idx regclass;
+    p_b REAL;
+    p_k1 REAL;
+    p_words INT;
+    p_docs INT;
     p_dims INT;
+    p_idf TEXT;
+    p_vocabulary TEXT;
+    p_hash_bits INT;
+    p_hash_seed INT;
+    p_tokenizer TEXT;
+    p_model TEXT;
+BEGIN
+    SELECT genrelid, indexrelid, b, k1, words, docs, dims, idf, vocabulary, coalesce(hash_bits, 0), coalesce(hash_seed, 0), tokenizer, model INTO gen, idx, p_b, p_k1, p_words, p_docs, p_dims, p_idf, p_vocabulary, p_hash_bits, p_hash_seed, p_tokenizer, p_model FROM bm_catalog.pg_bm25 WHERE matrelid = mat;
+    IF generation != 0 THEN
+        SELECT g.genrelid, g.indexrelid, g.b, g.k1, g.words, g.docs, g.dims, g.idf INTO gen, idx, p_b, p_k1, p_words, p_docs, p_dims, p_idf FROM bm_catalog.pg_bm25_generations g WHERE g.matrelid = mat AND g.generation = bm25_structured_query_to_svector.generation;
+        IF NOT FOUND THEN
+            RAISE EXCEPTION 'Generation % of `%` is not retained.', generation, mat;
+        END IF;
+    END IF;
+    RETURN bm_catalog.bm25_structured_query_to_svector_internal(gen::oid, idx::oid, p_b, p_k1, p_words, p_docs, p_dims, p_idf, t, style, p_vocabulary, p_hash_bits, p_hash_seed, p_tokenizer, p_model);
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_structured_query_match(mat regclass, document TEXT, query TEXT) RETURNS BOOLEAN STABLE STRICT PARALLEL SAFE AS $fn$
+DECLARE
+    p_tokenizer TEXT;
+    p_model TEXT;
+BEGIN
+    SELECT tokenizer, model INTO p_tokenizer, p_model FROM bm_catalog.pg_bm25 WHERE matrelid = mat;
+    RETURN bm_catalog.structured_query_match(document, query, p_tokenizer, p_model);
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_phrase_score(mat regclass, document TEXT, query TEXT, window_size INT DEFAULT 8) RETURNS REAL STABLE STRICT PARALLEL SAFE AS $fn$
+DECLARE
+    p_tokenizer TEXT;
+    p_model TEXT;
+BEGIN
+    SELECT tokenizer, model INTO p_tokenizer, p_model FROM bm_catalog.pg_bm25 WHERE matrelid = mat;
+    RETURN bm_catalog.phrase_score(document, query, p_tokenizer, p_model, window_size);
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE FUNCTION bm25_tune(mat regclass, queries regclass, query_col TEXT, qrels regclass, b_grid REAL[] DEFAULT '{0.3, 0.5, 0.75, 0.9}', k1_grid REAL[] DEFAULT '{0.9, 1.2, 1.5, 2.0}', k INT DEFAULT 10, query_id_col TEXT DEFAULT 'id', doc_id_col TEXT DEFAULT 'id') RETURNS TABLE(b REAL, k1 REAL, recall REAL, mrr REAL, ndcg REAL, best BOOLEAN) STABLE STRICT AS $fn$
+DECLARE
+    tab regclass;
+    col NAME;
+    gen regclass;
+    idx regclass;
+    p_words INT;
+    p_docs INT;
+    p_idf TEXT;
+    p_vocabulary TEXT;
+    p_hash_bits INT;
+    p_hash_seed INT;
     p_tokenizer TEXT;
     p_model TEXT;
 BEGIN
-    SELECT indexrelid, dims, tokenizer, model INTO idx, p_dims, p_tokenizer, p_model FROM bm_catalog.pg_bm25 WHERE matrelid = mat;
-    RETURN bm_catalog.bm25_query_to_svector_internal(mat::oid, idx::oid, p_dims, t, style, p_tokenizer, p_model);
+    SELECT p.attrelid, p.attname, p.genrelid, p.indexrelid, p.words, p.docs, p.idf, p.vocabulary, coalesce(p.hash_bits, 0), coalesce(p.hash_seed, 0), p.tokenizer, p.model INTO tab, col, gen, idx, p_words, p_docs, p_idf, p_vocabulary, p_hash_bits, p_hash_seed, p_tokenizer, p_model FROM bm_catalog.pg_bm25 p WHERE p.matrelid = mat;
+    IF NOT FOUND THEN
+        RAISE EXCEPTION 'There is no BM25 statistics `%`.', mat;
+    END IF;
+    IF tab IS NULL THEN
+        RAISE EXCEPTION 'BM25 statistics `%` have no table to tune on.', mat;
+    END IF;
+    RETURN QUERY
+        SELECT t.b, t.k1, t.recall, t.mrr, t.ndcg, row_number() OVER (ORDER BY t.ndcg DESC, t.mrr DESC, t.recall DESC) = 1
+        FROM bm_catalog.bm25_tune_internal(gen::oid, idx::oid, p_words, p_docs, p_idf,
+            tab::oid, doc_id_col, col, queries::oid, query_id_col, query_col, qrels::oid,
+            b_grid, k1_grid, k, p_vocabulary, p_hash_bits, p_hash_seed, p_tokenizer, p_model) t
+        ORDER BY t.b, t.k1;
 END;
 $fn$ LANGUAGE plpgsql;
+
+-- Links the catalog to the relations of a restored dump or of a subscriber, where indexes have new
+-- oids and columns may have new numbers, points the view of each statistics at its current
+-- generation, and restarts counting changes. Returns the number of statistics.
+CREATE FUNCTION bm25_restore() RETURNS INT AS $fn$
+DECLARE
+    restored INT;
+    s RECORD;
+BEGIN
+    UPDATE bm_catalog.pg_bm25_generations g
+    SET indexrelid = bm_catalog.bm25_index_of(g.genrelid);
+    UPDATE bm_catalog.pg_bm25 p
+    SET indexrelid = g.indexrelid, attnum = bm_catalog.bm25_attnum(p.attrelid, p.attname), baseline_changes = coalesce(bm_catalog.bm25_table_changes(p.attrelid), 0)
+    FROM bm_catalog.pg_bm25_generations g
+    WHERE g.matrelid = p.matrelid AND g.generation = p.generation;
+    GET DIAGNOSTICS restored = ROW_COUNT;
+    UPDATE bm_catalog.pg_bm25_columns c
+    SET attnum = bm_catalog.bm25_attnum(c.attrelid, c.attname), sourcenum = bm_catalog.bm25_attnum(c.attrelid, c.source), keynum = bm_catalog.bm25_attnum(c.attrelid, c.keyname);
+    -- refreshes on a publisher replicate the catalog but not the view
+    FOR s IN SELECT x.matrelid, x.genrelid FROM bm_catalog.pg_bm25 x LOOP
+        EXECUTE format('CREATE OR REPLACE VIEW %s AS SELECT * FROM %s', s.matrelid, s.genrelid);
+    END LOOP;
+    RETURN restored;
+END;
+$fn$ LANGUAGE plpgsql;
+
+-- The catalog refers to tables, columns and views by oid and attnum without dependencies on them, so
+-- event triggers keep it consistent with DDL. Dropping the table, the source column or the view of
+-- statistics drops the statistics, dropping a generation by hand forgets it, and dropping a table or
+-- column of a column of document vectors forgets the column. Indexes of generations may only be
+-- dropped together with their generation. Event triggers run for the DDL of every user, so they
+-- run as the owner of the catalog, and return at once unless the DDL touches statistics.
+CREATE FUNCTION bm25_sql_drop() RETURNS event_trigger SECURITY DEFINER SET search_path = pg_catalog, pg_temp AS $fn$
+DECLARE
+    d RECORD;
+    s RECORD;
+    rel regclass;
+    gens regclass[];
+    view_nsp NAME;
+    view_name NAME;
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_catalog.pg_event_trigger_dropped_objects() x
+        WHERE x.classid = 'pg_catalog.pg_class'::regclass AND x.objid IN (
+            SELECT p.matrelid::oid FROM bm_catalog.pg_bm25 p
+            UNION ALL SELECT p.genrelid::oid FROM bm_catalog.pg_bm25 p
+            UNION ALL SELECT p.attrelid::oid FROM bm_catalog.pg_bm25 p
+            UNION ALL SELECT g.genrelid::oid FROM bm_catalog.pg_bm25_generations g
+            UNION ALL SELECT g.indexrelid FROM bm_catalog.pg_bm25_generations g
+            UNION ALL SELECT c.attrelid::oid FROM bm_catalog.pg_bm25_columns c
+        )
+    ) THEN
+        RETURN;
+    END IF;
+    FOR d IN
+        SELECT x.object_identity FROM pg_catalog.pg_event_trigger_dropped_objects() x
+        JOIN bm_catalog.pg_bm25_generations g ON x.objid = g.indexrelid::oid
+        WHERE x.classid = 'pg_catalog.pg_class'::regclass AND x.objsubid = 0
+        AND NOT EXISTS (SELECT 1 FROM pg_catalog.pg_event_trigger_dropped_objects() y WHERE y.classid = 'pg_catalog.pg_class'::regclass AND y.objid = g.genrelid::oid)
+    LOOP
+        RAISE EXCEPTION 'Index `%` belongs to BM25 statistics.', d.object_identity
+            USING HINT = 'Use bm25_drop to drop the statistics.';
+    END LOOP;
+    FOR s IN
+        SELECT p.matrelid FROM bm_catalog.pg_bm25 p
+        WHERE EXISTS (
+            SELECT 1 FROM pg_catalog.pg_event_trigger_dropped_objects() x
+            WHERE x.classid = 'pg_catalog.pg_class'::regclass
+            AND (x.objid IN (p.matrelid::oid, p.genrelid::oid, p.attrelid::oid) AND x.objsubid = 0 OR x.objid = p.attrelid::oid AND x.objsubid = p.attnum)
+        )
+    LOOP
+        -- the name of the view, from the dropped objects if it is one of them
+        SELECT n.nspname, c.relname INTO view_nsp, view_name
+        FROM pg_catalog.pg_class c JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace WHERE c.oid = s.matrelid::oid;
+        IF NOT FOUND THEN
+            SELECT x.schema_name, x.object_name INTO view_nsp, view_name FROM pg_catalog.pg_event_trigger_dropped_objects() x
+            WHERE x.classid = 'pg_catalog.pg_class'::regclass AND x.objid = s.matrelid::oid AND x.objsubid = 0;
+        END IF;
+        -- the catalog goes first, so that the drops below find nothing to clean up
+        SELECT array_agg(c.oid::regclass) INTO gens
+        FROM bm_catalog.pg_bm25_generations g
+        JOIN pg_catalog.pg_class c ON c.oid = g.genrelid::oid
+        JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+        WHERE g.matrelid = s.matrelid AND n.nspname = view_nsp AND c.relname = view_name || '_g' || g.generation;
+        DELETE FROM bm_catalog.pg_bm25_generations g WHERE g.matrelid = s.matrelid;
+        DELETE FROM bm_catalog.pg_bm25_columns c WHERE c.matrelid = s.matrelid;
+        DELETE FROM bm_catalog.pg_bm25 p WHERE p.matrelid = s.matrelid;
+        -- without CASCADE, objects of users that depend on the statistics make the drop fail
+        FOR rel IN SELECT c.oid::regclass FROM pg_catalog.pg_class c WHERE c.oid = s.matrelid::oid LOOP
+            EXECUTE format('DROP VIEW %s', rel);
+        END LOOP;
+        FOREACH rel IN ARRAY coalesce(gens, '{}') LOOP
+            EXECUTE format('DROP TABLE %s', rel);
+        END LOOP;
+    END LOOP;
+    DELETE FROM bm_catalog.pg_bm25_generations g
+    USING pg_catalog.pg_event_trigger_dropped_objects() x
+    WHERE x.classid = 'pg_catalog.pg_class'::regclass AND x.objid = g.genrelid::oid AND x.objsubid = 0;
+    DELETE FROM bm_catalog.pg_bm25_columns c
+    USING pg_catalog.pg_event_trigger_dropped_objects() x
+    WHERE x.classid = 'pg_catalog.pg_class'::regclass AND x.objid = c.attrelid::oid
+    AND (x.objsubid = 0 OR x.objsubid IN (c.attnum, c.sourcenum, c.keynum));
+END;
+$fn$ LANGUAGE plpgsql;
+
+-- Follows renames of columns.
+CREATE FUNCTION bm25_ddl_command_end() RETURNS event_trigger SECURITY DEFINER SET search_path = pg_catalog, pg_temp AS $fn$
+DECLARE
+    altered oid[];
+BEGIN
+    SELECT array_agg(x.objid) INTO altered FROM pg_catalog.pg_event_trigger_ddl_commands() x
+    WHERE x.classid = 'pg_catalog.pg_class'::regclass
+    AND (x.objid IN (SELECT p.attrelid::oid FROM bm_catalog.pg_bm25 p) OR x.objid IN (SELECT c.attrelid::oid FROM bm_catalog.pg_bm25_columns c));
+    IF altered IS NULL THEN
+        RETURN;
+    END IF;
+    UPDATE bm_catalog.pg_bm25 p SET attname = a.attname
+    FROM pg_catalog.pg_attribute a
+    WHERE p.attrelid::oid = ANY(altered) AND a.attrelid = p.attrelid AND a.attnum = p.attnum AND a.attname <> p.attname;
+    UPDATE bm_catalog.pg_bm25_columns c SET
+        attname = (SELECT a.attname FROM pg_catalog.pg_attribute a WHERE a.attrelid = c.attrelid AND a.attnum = c.attnum),
+        source = (SELECT a.attname FROM pg_catalog.pg_attribute a WHERE a.attrelid = c.attrelid AND a.attnum = c.sourcenum),
+        keyname = (SELECT a.attname FROM pg_catalog.pg_attribute a WHERE a.attrelid = c.attrelid AND a.attnum = c.keynum)
+    WHERE c.attrelid::oid = ANY(altered);
+END;
+$fn$ LANGUAGE plpgsql;
+
+CREATE EVENT TRIGGER bm25_sql_drop ON sql_drop
+    EXECUTE FUNCTION bm_catalog.bm25_sql_drop();
+
+CREATE EVENT TRIGGER bm25_ddl_command_end ON ddl_command_end
+    WHEN TAG IN ('ALTER TABLE')
+    EXECUTE FUNCTION bm_catalog.bm25_ddl_command_end();
@@ -1,6 +1,9 @@
+mod multilingual;
+
 use std::{
     collections::{HashMap, HashSet},
-    sync::{Mutex, OnceLock},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
 };
 
 struct WhitespaceTokenizer;
@@ -9,34 +12,238 @@ struct HFTokenizer {
     tokenizer: tokenizers::Tokenizer,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JiebaMode {
+    Cut,
+    CutAll,
+    CutForSearch,
+}
+
 struct JiebaTokenizer {
     jeiba: jieba_rs::Jieba,
+    mode: JiebaMode,
+    hmm: bool,
 }
 
 struct TiniestsegmenterTokenizer;
 
 struct TiktokenTokenizer {
     tokenizer: tiktoken_rs::CoreBPE,
+    vocab_size: u32,
+    pieces: bool,
+}
+
+/// The `model` argument of a tokenizer, split on `,`. The item without `=` names the model,
+/// items with `=` are options, e.g. `cl100k_base,output=pieces` or `mode=search,hmm=false`.
+struct Spec<'a> {
+    name: &'a str,
+    options: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Spec<'a> {
+    fn parse(model: &'a str) -> Spec<'a> {
+        let mut name = "";
+        let mut options = Vec::new();
+        for item in model.split(',').map(str::trim) {
+            match item.split_once('=') {
+                Some((key, value)) => options.push((key.trim(), value.trim())),
+                None if !item.is_empty() => name = item,
+                None => (),
+            }
+        }
+        Spec { name, options }
+    }
+
+    fn option(&self, key: &str) -> Option<&'a str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| *v)
+    }
+
+    fn flag(&self, key: &str, default: bool) -> bool {
+        match self.option(key) {
+            None => default,
+            Some("true" | "on" | "1") => true,
+            Some("false" | "off" | "0") => false,
+            Some(value) => panic!("Invalid value for option {key}: {value}"),
+        }
+    }
 }
 
 impl JiebaTokenizer {
-    fn new() -> JiebaTokenizer {
+    // The model name is ignored, so statistics created with the default `model` keep working.
+    fn new(model: &str) -> JiebaTokenizer {
+        let dict_dir = crate::guc::JIEBA_DICT_DIR
+            .get()
+            .map(|dir| PathBuf::from(&*dir.to_string_lossy()));
+        JiebaTokenizer::with_dict_dir(model, dict_dir.as_deref())
+    }
+
+    fn with_dict_dir(model: &str, dict_dir: Option<&Path>) -> JiebaTokenizer {
+        let spec = Spec::parse(model);
+        let mut jeiba = jieba_rs::Jieba::new();
+        for (key, value) in spec.options.iter() {
+            match *key {
+                "dict" => {
+                    let content = std::fs::read_to_string(dict_path(dict_dir, value))
+                        .unwrap_or_else(|e| panic!("failed to read jieba dictionary {value}: {e}"));
+                    for line in content.lines() {
+                        let mut fields = line.split_whitespace();
+                        if let Some(word) = fields.next() {
+                            let freq = fields.next().map(|f| {
+                                f.parse::<usize>()
+                                    .unwrap_or_else(|_| panic!("Invalid frequency in {value}: {f}"))
+                            });
+                            jeiba.add_word(word, freq, fields.next());
+                        }
+                    }
+                }
+                "dict_table" => {
+                    for (word, freq, tag) in load_dict_table(value) {
+                        jeiba.add_word(&word, freq, tag.as_deref());
+                    }
+                }
+                _ => (),
+            }
+        }
+        let mode = match spec.option("mode").unwrap_or("cut") {
+            "cut" => JiebaMode::Cut,
+            "all" | "cut_all" => JiebaMode::CutAll,
+            "search" | "cut_for_search" => JiebaMode::CutForSearch,
+            mode => panic!("Unknown jieba mode: {mode}"),
+        };
         JiebaTokenizer {
-            jeiba: jieba_rs::Jieba::new(),
+            jeiba,
+            mode,
+            hmm: spec.flag("hmm", true),
         }
     }
 }
 
+/// The path of dictionary file `name` in `dir`, which only superusers can set. `name` must be a
+/// relative path that stays inside it, so that the option reads no other file of the server.
+fn dict_path(dir: Option<&Path>, name: &str) -> PathBuf {
+    let Some(dir) = dir else {
+        panic!("jieba dictionary files need pg_bestmatch.jieba_dict_dir, use dict_table instead");
+    };
+    let path = Path::new(name);
+    if name.is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        panic!("Invalid jieba dictionary {name}: must be a relative path without `..`");
+    }
+    dir.join(path)
+}
+
+/// Reads `word`, `freq` and `tag` columns of a user dictionary table. `freq` and `tag` may be
+/// null, in which case jieba suggests a frequency that keeps the word together.
+fn load_dict_table(table: &str) -> Vec<(String, Option<usize>, Option<String>)> {
+    let query = format!(
+        "SELECT word::text, freq::int8, tag::text FROM {}",
+        dict_table_name(table)
+    );
+    pgrx::spi::Spi::connect(|client| {
+        let mut result = Vec::new();
+        for row in client.select(&query, None, None).unwrap() {
+            let word = row.get::<String>(1).unwrap();
+            let freq = row.get::<i64>(2).unwrap();
+            let tag = row.get::<String>(3).unwrap();
+            if let Some(word) = word {
+                result.push((word, freq.map(|f| f as usize), tag));
+            }
+        }
+        result
+    })
+}
+
+/// The quoted name of a dictionary table, which is looked up as a `regclass` so that the option
+/// cannot inject SQL.
+fn dict_table_name(table: &str) -> String {
+    use pgrx::IntoDatum;
+    pgrx::spi::Spi::get_one_with_args::<String>(
+        "SELECT $1::regclass::text",
+        vec![(pgrx::PgBuiltInOids::TEXTOID.oid(), table.into_datum())],
+    )
+    .unwrap()
+    .unwrap()
+}
+
+/// Digests of dictionary tables by the command that read them.
+#[allow(clippy::type_complexity)]
+static DICT_TABLE_DIGESTS: OnceLock<Mutex<HashMap<String, ((u32, i64, u32), String)>>> =
+    OnceLock::new();
+
+/// MD5 of the rows of a dictionary table. It is read once per command, which sees the same rows
+/// throughout.
+fn dict_table_digest(table: &str) -> String {
+    let command = unsafe {
+        #[cfg(not(feature = "pg17"))]
+        let lxid = (*pgrx::pg_sys::MyProc).lxid;
+        #[cfg(feature = "pg17")]
+        let lxid = (*pgrx::pg_sys::MyProc).vxid.lxid;
+        (
+            lxid,
+            pgrx::pg_sys::GetCurrentStatementStartTimestamp(),
+            pgrx::pg_sys::GetCurrentCommandId(false),
+        )
+    };
+    let mut digests = DICT_TABLE_DIGESTS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e: std::sync::PoisonError<_>| e.into_inner());
+    if let Some((read, digest)) = digests.get(table) {
+        if *read == command {
+            return digest.clone();
+        }
+    }
+    let query = format!(
+        "SELECT md5(coalesce(string_agg((word::text, freq::int8, tag::text)::text, E'\\n'
+            ORDER BY word::text, freq::int8, tag::text), '')) FROM {}",
+        dict_table_name(table)
+    );
+    let digest = pgrx::spi::Spi::get_one::<String>(&query).unwrap().unwrap();
+    digests.insert(table.to_string(), (command, digest.clone()));
+    digest
+}
+
+/// The cache key of a `jieba` or `multilingual` model. It includes the digests of its dictionary
+/// tables, so that the tokenizer is loaded again when one of them changes.
+fn dict_cache_key(model: &str) -> String {
+    let mut key = model.to_string();
+    for (_, table) in Spec::parse(model)
+        .options
+        .iter()
+        .filter(|(key, _)| *key == "dict_table")
+    {
+        key.push('\0');
+        key.push_str(&dict_table_digest(table));
+    }
+    key
+}
+
 impl HFTokenizer {
     pub fn new(model: &str) -> HFTokenizer {
-        HFTokenizer {
-            tokenizer: tokenizers::tokenizer::Tokenizer::from_pretrained(model, None).unwrap(),
-        }
+        let spec = Spec::parse(model);
+        let local = crate::guc::TOKENIZER_MODEL_DIR
+            .get()
+            .map(|dir| {
+                std::path::Path::new(&*dir.to_string_lossy())
+                    .join(spec.name)
+                    .join("tokenizer.json")
+            })
+            .filter(|path| path.is_file());
+        let tokenizer = match local {
+            Some(path) => tokenizers::tokenizer::Tokenizer::from_file(path).unwrap(),
+            None => tokenizers::tokenizer::Tokenizer::from_pretrained(spec.name, None).unwrap(),
+        };
+        HFTokenizer { tokenizer }
     }
 }
 
 impl TiktokenTokenizer {
     fn new(model: &str) -> TiktokenTokenizer {
+        let spec = Spec::parse(model);
+        let model = spec.name;
         let selected_model = tiktoken_rs::tokenizer::get_tokenizer(model)
             .map(|tokenizer| match tokenizer {
                 tiktoken_rs::tokenizer::Tokenizer::O200kBase => "o200k_base",
@@ -57,6 +264,19 @@ impl TiktokenTokenizer {
                 "r50k_base" | "gpt2" => tiktoken_rs::r50k_base().unwrap(),
                 _ => panic!("Unknown model"),
             },
+            // the largest rank plus one, including special tokens
+            vocab_size: match selected_model {
+                "o200k_base" => 200019,
+                "cl100k_base" => 100277,
+                "p50k_base" => 50281,
+                "p50k_edit" => 50284,
+                _ => 50257,
+            },
+            pieces: match spec.option("output").unwrap_or("ranks") {
+                "ranks" => false,
+                "pieces" => true,
+                output => panic!("Unknown tiktoken output: {output}"),
+            },
         }
     }
 }
@@ -66,6 +286,15 @@ pub trait Tokenize {
     fn tokenize(&self, s: &str) -> Vec<String> {
         s.split_whitespace().map(|s| s.to_string()).collect()
     }
+
+    // only tokenizers with a fixed vocabulary have native ids
+    fn tokenize_ids(&self, _s: &str) -> Option<Vec<u32>> {
+        None
+    }
+
+    fn vocab_size(&self) -> Option<u32> {
+        None
+    }
 }
 
 impl Tokenize for WhitespaceTokenizer {}
@@ -78,15 +307,30 @@ impl Tokenize for HFTokenizer {
             .get_tokens()
             .to_vec()
     }
+
+    fn tokenize_ids(&self, s: &str) -> Option<Vec<u32>> {
+        Some(
+            self.tokenizer
+                .encode(s, false)
+                .expect("failed to tokenize")
+                .get_ids()
+                .to_vec(),
+        )
+    }
+
+    fn vocab_size(&self) -> Option<u32> {
+        Some(self.tokenizer.get_vocab_size(true) as u32)
+    }
 }
 
 impl Tokenize for JiebaTokenizer {
     fn tokenize(&self, s: &str) -> Vec<String> {
-        self.jeiba
-            .cut(s, true)
-            .iter()
-            .map(|s| s.to_string())
-            .collect()
+        let words = match self.mode {
+            JiebaMode::Cut => self.jeiba.cut(s, self.hmm),
+            JiebaMode::CutAll => self.jeiba.cut_all(s),
+            JiebaMode::CutForSearch => self.jeiba.cut_for_search(s, self.hmm),
+        };
+        words.iter().map(|s| s.to_string()).collect()
     }
 }
 
@@ -101,30 +345,45 @@ impl Tokenize for TiniestsegmenterTokenizer {
 
 impl Tokenize for TiktokenTokenizer {
     fn tokenize(&self, s: &str) -> Vec<String> {
-        self.tokenizer
-            .encode(s, HashSet::new())
-            .into_iter()
-            .map(|s| s.to_string())
-            .collect()
+        let ranks = self.tokenizer.encode(s, HashSet::new()).into_iter();
+        if self.pieces {
+            ranks
+                .map(|rank| {
+                    String::from_utf8_lossy(&self.tokenizer._decode_native(&[rank])).into_owned()
+                })
+                .collect()
+        } else {
+            ranks.map(|s| s.to_string()).collect()
+        }
+    }
+
+    fn tokenize_ids(&self, s: &str) -> Option<Vec<u32>> {
+        Some(
+            self.tokenizer
+                .encode(s, HashSet::new())
+                .into_iter()
+                .map(|rank| rank as u32)
+                .collect(),
+        )
+    }
+
+    fn vocab_size(&self) -> Option<u32> {
+        Some(self.vocab_size)
     }
 }
 
-type PostgresTokenizer = Box<dyn Tokenize + Sync + Send>;
+type PostgresTokenizer = Arc<dyn Tokenize + Sync + Send>;
 type MultiOL = OnceLock<Mutex<HashMap<String, PostgresTokenizer>>>;
 type SingleOL = OnceLock<PostgresTokenizer>;
 
 static HF_TOKENIZER: MultiOL = OnceLock::new();
 static TIKTOKEN_TOKENIZER: MultiOL = OnceLock::new();
-static JIEBA_TOKENIZER: SingleOL = OnceLock::new();
+static JIEBA_TOKENIZER: MultiOL = OnceLock::new();
+static MULTILINGUAL_TOKENIZER: MultiOL = OnceLock::new();
 static TINIESTSEGMENTER_TOKENIZER: SingleOL = OnceLock::new();
 static WHITESPACE_TOKENIZER: SingleOL = OnceLock::new();
 
-fn _hashmap_tokenize<T>(
-    lock: &MultiOL,
-    model: &str,
-    new_fn: impl Fn(&str) -> T,
-    s: &str,
-) -> Vec<String>
+fn _hashmap_with<T>(lock: &MultiOL, key: &str, new_fn: impl FnOnce() -> T) -> PostgresTokenizer
 where
     T: Tokenize + Sync + Send + 'static,
 {
@@ -134,36 +393,127 @@ where
         // on panic, the mutex gets poisoned, so we need a way to handle it.
         .unwrap_or_else(|e: std::sync::PoisonError<_>| e.into_inner());
 
+    // drop an arbitrary tokenizer to keep at most `vocab_cache_size` of them
+    let capacity = crate::guc::VOCAB_CACHE_SIZE.get().max(1) as usize;
+    if !lock_guard.contains_key(key) && lock_guard.len() >= capacity {
+        if let Some(evicted) = lock_guard.keys().next().cloned() {
+            lock_guard.remove(&evicted);
+        }
+    }
+
+    // the lock is released on return, so that threads of a build don't wait for each other
     lock_guard
-        .entry(model.to_string())
-        .or_insert_with(|| Box::new(new_fn(model)))
-        .tokenize(s)
+        .entry(key.to_string())
+        .or_insert_with(|| Arc::new(new_fn()))
+        .clone()
 }
 
-pub fn tokenize(tokenizer: &str, model: Option<&str>, s: &str) -> Vec<String> {
-    let selected_tokenizer = match tokenizer {
+fn get_tokenizer(tokenizer: &str, model: Option<&str>) -> PostgresTokenizer {
+    match tokenizer {
         "hf" => {
             let selected_model = model.expect("model must be provided for hf tokenizer");
-            return _hashmap_tokenize(&HF_TOKENIZER, selected_model, HFTokenizer::new, s);
+            _hashmap_with(&HF_TOKENIZER, selected_model, || {
+                HFTokenizer::new(selected_model)
+            })
         }
         "tiktoken" => {
             let selected_model = model.expect("model or encoding must be provided");
-            return _hashmap_tokenize(
-                &TIKTOKEN_TOKENIZER,
-                selected_model,
-                TiktokenTokenizer::new,
-                s,
-            );
+            _hashmap_with(&TIKTOKEN_TOKENIZER, selected_model, || {
+                TiktokenTokenizer::new(selected_model)
+            })
+        }
+        "multilingual" => {
+            let selected_model = model.unwrap_or_default();
+            _hashmap_with(
+                &MULTILINGUAL_TOKENIZER,
+                &dict_cache_key(selected_model),
+                || multilingual::MultilingualTokenizer::new(selected_model),
+            )
         }
-        "ws" => WHITESPACE_TOKENIZER.get_or_init(|| Box::new(WhitespaceTokenizer)),
-        "jieba" => JIEBA_TOKENIZER.get_or_init(|| Box::new(JiebaTokenizer::new())),
-        "tiniestsegmenter" => {
-            TINIESTSEGMENTER_TOKENIZER.get_or_init(|| Box::new(TiniestsegmenterTokenizer))
+        "ws" => WHITESPACE_TOKENIZER
+            .get_or_init(|| Arc::new(WhitespaceTokenizer))
+            .clone(),
+        "jieba" => {
+            let selected_model = model.unwrap_or_default();
+            _hashmap_with(&JIEBA_TOKENIZER, &dict_cache_key(selected_model), || {
+                JiebaTokenizer::new(selected_model)
+            })
         }
+        "tiniestsegmenter" => TINIESTSEGMENTER_TOKENIZER
+            .get_or_init(|| Arc::new(TiniestsegmenterTokenizer))
+            .clone(),
         _ => panic!("Unknown tokenizer"),
-    };
+    }
+}
+
+/// A tokenizer with its options. Loading it may read dictionary tables with SPI, but using it
+/// does not call into Postgres, so threads can share it.
+pub struct Loaded {
+    tokenizer: PostgresTokenizer,
+    bigrams: bool,
+}
+
+pub fn load(tokenizer: &str, model: Option<&str>) -> Loaded {
+    Loaded {
+        tokenizer: get_tokenizer(tokenizer, model),
+        bigrams: has_bigrams(model),
+    }
+}
+
+/// Whether the `bigrams` option of a model is set.
+pub fn has_bigrams(model: Option<&str>) -> bool {
+    Spec::parse(model.unwrap_or_default()).flag("bigrams", false)
+}
+
+impl Loaded {
+    /// Tokens in order of position, without bigrams.
+    pub fn tokenize_unigrams(&self, s: &str) -> Vec<String> {
+        self.tokenizer.tokenize(s)
+    }
+
+    /// Tokens, followed by bigrams joined by a space if the `bigrams` option is set.
+    pub fn tokenize(&self, s: &str) -> Vec<String> {
+        let mut tokens = self.tokenize_unigrams(s);
+        if self.bigrams {
+            let bigrams = tokens
+                .windows(2)
+                .map(|pair| format!("{} {}", pair[0], pair[1]))
+                .collect::<Vec<_>>();
+            tokens.extend(bigrams);
+        }
+        tokens
+    }
+
+    /// Native token ids of `hf` and `tiktoken`, or `None` for other tokenizers.
+    pub fn tokenize_ids(&self, s: &str) -> Option<Vec<u32>> {
+        if self.bigrams {
+            panic!("Bigrams have no native token ids");
+        }
+        self.tokenizer.tokenize_ids(s)
+    }
+}
+
+/// Tokens in order of position, without bigrams.
+pub fn tokenize_unigrams(tokenizer: &str, model: Option<&str>, s: &str) -> Vec<String> {
+    load(tokenizer, model).tokenize_unigrams(s)
+}
 
-    selected_tokenizer.tokenize(s)
+/// Tokens, followed by bigrams joined by a space if the `bigrams` option is set.
+pub fn tokenize(tokenizer: &str, model: Option<&str>, s: &str) -> Vec<String> {
+    load(tokenizer, model).tokenize(s)
+}
+
+/// Native token ids of `hf` and `tiktoken`, which can be used as dimensions without a vocabulary.
+pub fn tokenize_ids(tokenizer: &str, model: Option<&str>, s: &str) -> Vec<u32> {
+    load(tokenizer, model)
+        .tokenize_ids(s)
+        .unwrap_or_else(|| panic!("Tokenizer {tokenizer} has no native token ids"))
+}
+
+pub fn vocab_size(tokenizer: &str, model: Option<&str>) -> u32 {
+    get_tokenizer(tokenizer, model)
+        .vocab_size()
+        .unwrap_or_else(|| panic!("Tokenizer {tokenizer} has no fixed vocabulary"))
 }
 
 #[cfg(test)]
@@ -203,6 +553,84 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_jieba_options() {
+        let s = "小明硕士毕业于中国科学院计算所";
+        assert_eq!(
+            super::tokenize("jieba", Some("google-bert/bert-base-uncased"), s),
+            vec!["小明", "硕士", "毕业", "于", "中国科学院", "计算所"]
+        );
+        assert_eq!(
+            super::tokenize("jieba", Some("hmm=false"), s),
+            vec!["小", "明", "硕士", "毕业", "于", "中国科学院", "计算所"]
+        );
+        assert_eq!(
+            super::tokenize("jieba", Some("mode=search"), s),
+            vec![
+                "小明",
+                "硕士",
+                "毕业",
+                "于",
+                "中国",
+                "科学",
+                "学院",
+                "科学院",
+                "中国科学院",
+                "计算",
+                "计算所"
+            ]
+        );
+        assert_eq!(
+            super::tokenize("jieba", Some("mode=all"), "中国科学院"),
+            vec![
+                "中",
+                "中国",
+                "中国科学院",
+                "国",
+                "科",
+                "科学",
+                "科学院",
+                "学",
+                "学院",
+                "院"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jieba_dict() {
+        use super::Tokenize;
+        let dir = std::env::temp_dir();
+        std::fs::write(
+            dir.join("pg_bestmatch_jieba_dict.txt"),
+            "阿司匹林肠溶片 10 n\n",
+        )
+        .unwrap();
+        assert_eq!(
+            super::tokenize("jieba", None, "阿司匹林肠溶片的用法"),
+            vec!["阿司匹林", "肠溶片", "的", "用法"]
+        );
+        assert_eq!(
+            super::JiebaTokenizer::with_dict_dir("dict=pg_bestmatch_jieba_dict.txt", Some(&dir))
+                .tokenize("阿司匹林肠溶片的用法"),
+            vec!["阿司匹林肠溶片", "的", "用法"]
+        );
+    }
+
+    #[test]
+    fn test_jieba_dict_path() {
+        let dir = std::path::Path::new("/dicts");
+        assert_eq!(
+            super::dict_path(Some(dir), "medical/terms.txt"),
+            dir.join("medical/terms.txt")
+        );
+        for name in ["/etc/passwd", "../passwd", "medical/../../passwd", ""] {
+            let result = std::panic::catch_unwind(|| super::dict_path(Some(dir), name));
+            assert!(result.is_err(), "{name}");
+        }
+        assert!(std::panic::catch_unwind(|| super::dict_path(None, "terms.txt")).is_err());
+    }
+
     #[test]
     fn test_tiniestsegmenter() {
         assert_eq!(
@@ -227,6 +655,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_multilingual() {
+        assert_eq!(
+            super::tokenize(
+                "multilingual",
+                None,
+                "Testing versions: 测试版本将于秋季推出。"
+            ),
+            vec!["test", "version", "测试", "版本", "将", "于", "秋季", "推出"]
+        );
+        assert_eq!(
+            super::tokenize(
+                "multilingual",
+                Some("stemmer=none"),
+                "Zelda, 今作の主人公はリンクではなくゼルダ姫"
+            ),
+            vec![
+                "zelda",
+                "今作",
+                "の",
+                "主人",
+                "公",
+                "は",
+                "リンク",
+                "で",
+                "は",
+                "なく",
+                "ゼルダ",
+                "姫"
+            ]
+        );
+        // the Chinese run stays Chinese next to a Japanese one
+        assert_eq!(
+            super::tokenize("multilingual", None, "测试版本将于秋季推出。ゼルダ"),
+            vec!["测试", "版本", "将", "于", "秋季", "推出", "ゼルダ"]
+        );
+    }
+
     #[test]
     fn test_tiktoken() {
         // Test the encodings first
@@ -268,6 +734,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_tiktoken_options() {
+        assert_eq!(
+            super::tokenize(
+                "tiktoken",
+                Some("cl100k_base,output=pieces"),
+                "i want an apple"
+            ),
+            vec!["i", " want", " an", " apple"]
+        );
+        assert_eq!(
+            super::tokenize_ids("tiktoken", Some("cl100k_base"), "i want an apple"),
+            vec![72, 1390, 459, 24149]
+        );
+        assert_eq!(super::vocab_size("tiktoken", Some("cl100k_base")), 100277);
+        assert_eq!(
+            super::tokenize_ids("hf", Some("bert-base-uncased"), "i have an apple"),
+            vec![1045, 2031, 2019, 6207]
+        );
+        assert_eq!(super::vocab_size("hf", Some("bert-base-uncased")), 30522);
+    }
+
+    #[test]
+    fn test_bigrams() {
+        assert_eq!(
+            super::tokenize("ws", Some("bigrams=on"), "i have an"),
+            vec!["i", "have", "an", "i have", "have an"]
+        );
+        assert_eq!(
+            super::tokenize_unigrams("ws", Some("bigrams=on"), "i have an"),
+            vec!["i", "have", "an"]
+        );
+    }
+
     // panic
 
     #[test]
@@ -275,4 +775,10 @@ mod tests {
     fn test_tiktoken_panic() {
         super::tokenize("tiktoken", Some("foo"), "i want an apple");
     }
+
+    #[test]
+    #[should_panic]
+    fn test_tokenize_ids_panic() {
+        super::tokenize_ids("ws", None, "i want an apple");
+    }
 }
@@ -0,0 +1,102 @@
+use super::{JiebaTokenizer, Spec, TiniestsegmenterTokenizer, Tokenize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Han,
+    Kana,
+    Word,
+    Separator,
+}
+
+fn script(c: char) -> Script {
+    match c as u32 {
+        0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => Script::Kana,
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FA1F => Script::Han,
+        _ if c.is_alphanumeric() => Script::Word,
+        _ => Script::Separator,
+    }
+}
+
+/// Splits text into runs of the same script, dropping separators. Han and kana are kept in one
+/// run, because Japanese mixes both within a word.
+fn runs(s: &str) -> Vec<(bool, &str)> {
+    let mut result = Vec::new();
+    let mut start = None;
+    let mut cjk = false;
+    for (i, c) in s.char_indices() {
+        let current = script(c);
+        let current_cjk = matches!(current, Script::Han | Script::Kana);
+        if let Some(begin) = start {
+            if current == Script::Separator || current_cjk != cjk {
+                result.push((cjk, &s[begin..i]));
+                start = None;
+            }
+        }
+        if start.is_none() && current != Script::Separator {
+            start = Some(i);
+            cjk = current_cjk;
+        }
+    }
+    if let Some(begin) = start {
+        result.push((cjk, &s[begin..]));
+    }
+    result
+}
+
+pub struct MultilingualTokenizer {
+    jieba: JiebaTokenizer,
+    stemmer: Option<rust_stemmers::Stemmer>,
+}
+
+impl MultilingualTokenizer {
+    pub fn new(model: &str) -> MultilingualTokenizer {
+        use rust_stemmers::Algorithm;
+        let algorithm = match Spec::parse(model).option("stemmer").unwrap_or("english") {
+            "none" => None,
+            "arabic" => Some(Algorithm::Arabic),
+            "danish" => Some(Algorithm::Danish),
+            "dutch" => Some(Algorithm::Dutch),
+            "english" => Some(Algorithm::English),
+            "finnish" => Some(Algorithm::Finnish),
+            "french" => Some(Algorithm::French),
+            "german" => Some(Algorithm::German),
+            "greek" => Some(Algorithm::Greek),
+            "hungarian" => Some(Algorithm::Hungarian),
+            "italian" => Some(Algorithm::Italian),
+            "norwegian" => Some(Algorithm::Norwegian),
+            "portuguese" => Some(Algorithm::Portuguese),
+            "romanian" => Some(Algorithm::Romanian),
+            "russian" => Some(Algorithm::Russian),
+            "spanish" => Some(Algorithm::Spanish),
+            "swedish" => Some(Algorithm::Swedish),
+            "tamil" => Some(Algorithm::Tamil),
+            stemmer => panic!("Unknown stemmer: {stemmer}"),
+        };
+        MultilingualTokenizer {
+            jieba: JiebaTokenizer::new(model),
+            stemmer: algorithm.map(rust_stemmers::Stemmer::create),
+        }
+    }
+}
+
+impl Tokenize for MultilingualTokenizer {
+    // A run containing kana is Japanese, and a run of Han only is Chinese. Han and kana of a
+    // Japanese phrase are in one run, so a Chinese text quoting a Japanese name stays Chinese.
+    fn tokenize(&self, s: &str) -> Vec<String> {
+        let mut result = Vec::new();
+        for (cjk, run) in runs(s) {
+            if !cjk {
+                let word = run.to_lowercase();
+                match &self.stemmer {
+                    Some(stemmer) => result.push(stemmer.stem(&word).into_owned()),
+                    None => result.push(word),
+                }
+            } else if run.chars().any(|c| script(c) == Script::Kana) {
+                result.extend(TiniestsegmenterTokenizer.tokenize(run));
+            } else {
+                result.extend(self.jieba.tokenize(run));
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,60 @@
+use crate::guc;
+use pgrx::bgworkers::{BackgroundWorker, BackgroundWorkerBuilder, SignalWakeFlags};
+use std::time::Duration;
+
+/// Registers the maintenance worker. It only runs if the library is in `shared_preload_libraries`.
+pub fn init() {
+    if !unsafe { pgrx::pg_sys::process_shared_preload_libraries_in_progress } {
+        return;
+    }
+    BackgroundWorkerBuilder::new("pg_bestmatch maintenance")
+        .set_function("bm25_maintenance_main")
+        .set_library("pg_bestmatch")
+        .enable_spi_access()
+        .set_restart_time(Some(Duration::from_secs(10)))
+        .load();
+}
+
+#[pgrx::pg_guard]
+#[no_mangle]
+pub extern "C" fn bm25_maintenance_main(_arg: pgrx::pg_sys::Datum) {
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    let database = guc::MAINTENANCE_DATABASE
+        .get()
+        .map(|database| database.to_string_lossy().into_owned());
+    BackgroundWorker::connect_worker_to_spi(database.as_deref(), None);
+    loop {
+        let naptime = Duration::from_secs(guc::MAINTENANCE_NAPTIME.get() as u64);
+        if !BackgroundWorker::wait_latch(Some(naptime)) {
+            break;
+        }
+        if BackgroundWorker::sighup_received() {
+            unsafe {
+                pgrx::pg_sys::ProcessConfigFile(pgrx::pg_sys::GucContext::PGC_SIGHUP);
+            }
+        }
+        // standbys receive the statistics from the primary
+        if unsafe { pgrx::pg_sys::RecoveryInProgress() } {
+            continue;
+        }
+        BackgroundWorker::transaction(|| {
+            // the extension may not be installed in the database yet
+            let installed = pgrx::spi::Spi::get_one::<bool>(
+                "SELECT EXISTS (SELECT 1 FROM pg_catalog.pg_extension WHERE extname = 'pg_bestmatch')",
+            )
+            .unwrap()
+            .unwrap_or(false);
+            if installed {
+                let query = format!(
+                    "SELECT bm_catalog.bm25_maintain({}, {})",
+                    guc::REFRESH_THRESHOLD.get(),
+                    guc::MAINTENANCE_BATCH_SIZE.get()
+                );
+                // failures of single statistics are recorded by `bm25_maintain` itself
+                if let Err(e) = pgrx::spi::Spi::run(&query) {
+                    pgrx::log!("pg_bestmatch maintenance failed: {}", e);
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,58 @@
+SET search_path TO public, bm_catalog;
+SET client_min_messages TO warning;
+\set VERBOSITY terse
+
+CREATE TABLE docs (id INT PRIMARY KEY, passage TEXT, vec TEXT);
+INSERT INTO docs VALUES (1, 'apple pie', NULL), (2, 'cherry pie pie', NULL), (3, 'apple tart', NULL);
+CREATE TABLE notes (note_id INT PRIMARY KEY, body TEXT, vec TEXT);
+INSERT INTO notes SELECT i, 'note ' || i || ' pie', NULL FROM generate_series(1, 5) i;
+SELECT bm25_create('docs', 'passage', 'docs_bm25', tokenizer => 'ws', model => 'none');
+
+-- attached columns are pending until re-embedded in batches of keys
+SELECT bm25_attach('docs_bm25', 'notes', 'body', 'vec', 'pgvector', key_col => 'note_id');
+SELECT attrelid, attname, source, keyname, generation, pending, last_key FROM pg_bm25_columns;
+SELECT bm25_reembed_batch('docs_bm25', 2);
+SELECT generation, pending, last_key FROM pg_bm25_columns;
+SELECT note_id, vec IS NOT NULL AS embedded FROM notes ORDER BY note_id;
+SELECT bm25_reembed_batch('docs_bm25', 2);
+SELECT bm25_reembed_batch('docs_bm25', 2);
+SELECT generation, pending, last_key, embedded_at IS NOT NULL AS embedded FROM pg_bm25_columns;
+SELECT count(*) FILTER (WHERE vec IS DISTINCT FROM bm25_document_to_svector('docs_bm25', body, 'pgvector')) AS stale FROM notes;
+SELECT bm25_reembed_batch('docs_bm25', 2);
+
+-- a refresh makes them pending again, and they retain their generation until re-embedded
+INSERT INTO docs VALUES (4, 'plum pie', NULL);
+SELECT bm25_refresh('docs_bm25');
+SELECT bm25_refresh('docs_bm25');
+SELECT generation, pending, last_key FROM pg_bm25_columns;
+SELECT generation, columns FROM bm25_generations('docs_bm25');
+CALL bm25_reembed_attached('docs_bm25', 2);
+SELECT generation, pending, last_key FROM pg_bm25_columns;
+SELECT generation, columns FROM bm25_generations('docs_bm25');
+SELECT count(*) FILTER (WHERE vec IS DISTINCT FROM bm25_document_to_svector('docs_bm25', body, 'pgvector')) AS stale FROM notes;
+
+-- the column of the table of the statistics is re-embedded at once
+SELECT bm25_reembed('docs_bm25', 'vec', 'pgvector');
+SELECT attrelid, attname, generation, pending FROM pg_bm25_columns ORDER BY attrelid::TEXT;
+SELECT count(*) FILTER (WHERE vec IS DISTINCT FROM bm25_document_to_svector('docs_bm25', passage, 'pgvector')) AS stale FROM docs;
+
+-- detached columns no longer retain generations
+SELECT bm25_refresh('docs_bm25');
+SELECT bm25_refresh('docs_bm25');
+SELECT generation, columns FROM bm25_generations('docs_bm25');
+SELECT bm25_detach('docs_bm25', 'docs', 'vec');
+SELECT bm25_detach('docs_bm25', 'notes', 'vec');
+SELECT generation, columns FROM bm25_generations('docs_bm25');
+SELECT count(*) FROM pg_bm25_columns;
+
+-- failures
+SELECT bm25_attach('docs_bm25', 'notes', 'body', 'missing', 'pgvector', key_col => 'note_id');
+SELECT bm25_attach('docs_bm25', 'notes', 'body', 'vec', 'pgvector');
+SELECT bm25_attach('notes', 'notes', 'body', 'vec', 'pgvector', key_col => 'note_id');
+SELECT bm25_detach('docs_bm25', 'notes', 'vec');
+SELECT bm25_reembed_batch('docs_bm25', 0);
+SELECT bm25_reembed('docs_bm25', 'missing', 'pgvector');
+
+SELECT bm25_drop('docs_bm25');
+DROP TABLE notes;
+DROP TABLE docs;
@@ -0,0 +1,57 @@
+SET search_path TO public, bm_catalog;
+SET client_min_messages TO warning;
+\set VERBOSITY terse
+
+CREATE TABLE docs (id INT PRIMARY KEY, passage TEXT);
+INSERT INTO docs VALUES (1, 'apple pie'), (2, 'cherry pie pie');
+CREATE TABLE counts (token TEXT, df INT, cf INT);
+INSERT INTO counts VALUES ('apple', 10, 12), ('pie', 4, 4), ('plum', 6, 9), ('plum', 2, 2), (NULL, 5, 5), ('tart', 0, 0);
+
+-- a corpus vocabulary of tokens
+SELECT bm25_import_counts('wiki_bm25', 'counts', docs => 20, tokenizer => 'ws', model => 'none');
+SELECT token, id, df, cf FROM bm25_vocabulary('wiki_bm25');
+SELECT docs, words, dims, vocabulary FROM bm25_stats('wiki_bm25');
+SELECT bm25_create('docs', 'passage', 'docs_bm25', tokenizer => 'ws', model => 'none', background => 'wiki_bm25', background_weight => 0.5);
+SELECT token, df, cf FROM bm25_vocabulary('docs_bm25');
+SELECT docs, words, dims FROM bm25_stats('docs_bm25');
+INSERT INTO docs VALUES (3, 'plum');
+SELECT bm25_refresh('docs_bm25');
+SELECT token, df, cf FROM bm25_vocabulary('docs_bm25') WHERE token = 'plum';
+SELECT docs FROM bm25_stats('docs_bm25');
+
+-- a hashed vocabulary puts tokens into the buckets of the table
+SELECT bm25_import_counts('wiki_hashed', 'counts', docs => 20, tokenizer => 'ws', model => 'none', vocabulary => 'hashed', hash_bits => 8, hash_seed => 3);
+SELECT docs, words, dims, vocabulary FROM bm25_stats('wiki_hashed');
+SELECT bm25_create('docs', 'passage', 'docs_hashed', tokenizer => 'ws', model => 'none', vocabulary => 'hashed', hash_bits => 8, hash_seed => 3, background => 'wiki_hashed');
+SELECT token, df, cf FROM bm25_term_info('wiki_hashed', 'apple pie plum');
+SELECT token, df, cf FROM bm25_term_info('docs_hashed', 'apple pie plum');
+
+-- a fixed vocabulary takes token ids
+CREATE TABLE id_counts (id INT, df INT);
+INSERT INTO id_counts VALUES (7, 3), (7, 1), (42, 2);
+SELECT bm25_import_counts('wiki_fixed', 'id_counts', docs => 5, tokenizer => 'ws', model => 'none', vocabulary => 'fixed');
+SELECT id, df, cf FROM bm25_vocabulary('wiki_fixed');
+SELECT docs, words, dims, vocabulary FROM bm25_stats('wiki_fixed');
+
+-- failures
+SELECT bm25_create('docs', 'passage', 'docs_bad', tokenizer => 'ws', model => 'none', vocabulary => 'hashed', hash_bits => 8, background => 'wiki_bm25');
+SELECT bm25_create('docs', 'passage', 'docs_bad', tokenizer => 'ws', model => 'none', vocabulary => 'hashed', hash_bits => 9, hash_seed => 3, background => 'wiki_hashed');
+SELECT bm25_create('docs', 'passage', 'docs_bad', tokenizer => 'ws', model => 'none', background => 'wiki_bm25', background_weight => -1);
+SELECT bm25_import_counts('wiki_bad', 'counts', docs => 20, tokenizer => 'ws', model => 'none', vocabulary => 'unknown');
+SELECT bm25_import_counts('wiki_bad', 'counts', docs => 20, tokenizer => 'ws', model => 'none', vocabulary => 'fixed');
+SELECT bm25_import_counts('wiki_bad', 'id_counts', docs => 20, tokenizer => 'ws', model => 'none');
+SELECT bm25_import_counts('wiki_bad', 'counts', docs => 20, tokenizer => 'ws', model => 'none', vocabulary => 'hashed', hash_bits => 31);
+SELECT bm25_import_counts('wiki_bad', 'id_counts', docs => 20, tokenizer => 'ws', model => 'bigrams=on', vocabulary => 'fixed');
+INSERT INTO id_counts VALUES (1000, 1);
+SELECT bm25_import_counts('wiki_bad', 'id_counts', docs => 20, tokenizer => 'ws', model => 'none', vocabulary => 'fixed');
+SELECT bm25_import_counts('wiki_bad', 'counts', docs => 0, tokenizer => 'ws', model => 'none');
+SELECT count(*) FROM pg_class WHERE relname LIKE '%_bad%';
+
+SELECT bm25_drop('docs_bm25');
+SELECT bm25_drop('docs_hashed');
+SELECT bm25_drop('wiki_bm25');
+SELECT bm25_drop('wiki_hashed');
+SELECT bm25_drop('wiki_fixed');
+DROP TABLE id_counts;
+DROP TABLE counts;
+DROP TABLE docs;
@@ -0,0 +1,100 @@
+SET search_path TO public, bm_catalog;
+SET client_min_messages TO warning;
+\set VERBOSITY terse
+
+CREATE ROLE regress_bm25_user;
+GRANT CREATE ON SCHEMA public TO regress_bm25_user;
+
+-- DDL of users without access to the catalog, unrelated to statistics
+SET ROLE regress_bm25_user;
+CREATE TABLE notes (id INT PRIMARY KEY, body TEXT);
+CREATE INDEX notes_body ON notes (body);
+ALTER TABLE notes RENAME COLUMN body TO text;
+ALTER TABLE notes ADD COLUMN extra INT;
+ALTER TABLE notes DROP COLUMN extra;
+DROP INDEX notes_body;
+DROP TABLE notes;
+RESET ROLE;
+
+-- DDL of users on tables with statistics
+SET ROLE regress_bm25_user;
+CREATE TABLE docs (id INT PRIMARY KEY, passage TEXT, vec TEXT, other TEXT);
+INSERT INTO docs VALUES (1, 'apple pie', NULL, 'x'), (2, 'cherry pie pie', NULL, 'y');
+RESET ROLE;
+SELECT bm25_create('docs', 'passage', 'docs_bm25', tokenizer => 'ws', model => 'none');
+SELECT bm25_attach('docs_bm25', 'docs', 'passage', 'vec', 'pgvector');
+SET ROLE regress_bm25_user;
+ALTER TABLE docs RENAME COLUMN passage TO body;
+ALTER TABLE docs DROP COLUMN other;
+RESET ROLE;
+SELECT attrelid, attname FROM pg_bm25;
+SELECT attrelid, attname, source, keyname FROM pg_bm25_columns;
+SELECT bm25_refresh('docs_bm25');
+
+-- dropping the column of document vectors forgets it, and dropping the table the statistics
+SET ROLE regress_bm25_user;
+ALTER TABLE docs DROP COLUMN vec;
+RESET ROLE;
+SELECT count(*) FROM pg_bm25_columns;
+SET ROLE regress_bm25_user;
+DROP TABLE docs;
+RESET ROLE;
+SELECT count(*) FROM pg_bm25;
+SELECT count(*) FROM pg_bm25_generations;
+SELECT count(*) FROM pg_class WHERE relname LIKE 'docs_bm25%';
+
+-- generations dropped by hand are forgotten, and their indexes can't be dropped alone
+CREATE TABLE docs (id INT PRIMARY KEY, passage TEXT);
+INSERT INTO docs VALUES (1, 'apple pie'), (2, 'cherry pie pie');
+SELECT bm25_create('docs', 'passage', 'docs_bm25', tokenizer => 'ws', model => 'none');
+SELECT bm25_pin('docs_bm25', 1);
+SELECT bm25_refresh('docs_bm25');
+SELECT bm25_refresh('docs_bm25');
+SELECT bm25_refresh('docs_bm25');
+SELECT generation FROM bm25_generations('docs_bm25');
+DROP INDEX docs_bm25_g1_token_idx;
+DROP TABLE docs_bm25_g1;
+SELECT generation FROM bm25_generations('docs_bm25');
+DROP VIEW docs_bm25;
+SELECT count(*) FROM pg_bm25;
+SELECT count(*) FROM pg_class WHERE relname LIKE 'docs_bm25%';
+
+DROP TABLE docs;
+
+-- objects of users that depend on the statistics make the drop fail instead of going with it
+SET ROLE regress_bm25_user;
+CREATE TABLE docs (id INT PRIMARY KEY, passage TEXT);
+INSERT INTO docs VALUES (1, 'apple pie'), (2, 'cherry pie pie');
+RESET ROLE;
+SELECT bm25_create('docs', 'passage', 'docs_bm25', tokenizer => 'ws', model => 'none');
+SELECT bm25_refresh('docs_bm25');
+SET ROLE regress_bm25_user;
+CREATE VIEW terms AS SELECT token FROM docs_bm25;
+CREATE VIEW old_terms AS SELECT token FROM docs_bm25_g1;
+DROP TABLE docs;
+DROP VIEW terms;
+DROP TABLE docs;
+DROP VIEW old_terms;
+RESET ROLE;
+SELECT count(*) FROM pg_bm25;
+SELECT relname FROM pg_class WHERE relname LIKE 'docs_bm25%' AND relkind IN ('r', 'v') ORDER BY relname;
+SET ROLE regress_bm25_user;
+DROP TABLE docs;
+RESET ROLE;
+SELECT count(*) FROM pg_bm25;
+SELECT count(*) FROM pg_class WHERE relname LIKE 'docs_bm25%';
+
+-- only relations named as generations of the statistics are dropped with them
+CREATE TABLE docs (id INT PRIMARY KEY, passage TEXT);
+INSERT INTO docs VALUES (1, 'apple pie'), (2, 'cherry pie pie');
+CREATE TABLE precious (id INT);
+SELECT bm25_create('docs', 'passage', 'docs_bm25', tokenizer => 'ws', model => 'none');
+INSERT INTO pg_bm25_generations (matrelid, generation, genrelid, indexrelid, words, docs, dims, b, k1, idf, created_at)
+SELECT matrelid, 7, 'precious'::regclass, indexrelid, words, docs, dims, b, k1, idf, now() FROM pg_bm25;
+DROP TABLE docs;
+SELECT count(*) FROM pg_bm25_generations;
+SELECT relname FROM pg_class WHERE relname IN ('precious', 'docs_bm25', 'docs_bm25_g1') ORDER BY relname;
+DROP TABLE precious;
+
+REVOKE CREATE ON SCHEMA public FROM regress_bm25_user;
+DROP ROLE regress_bm25_user;
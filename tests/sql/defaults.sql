@@ -0,0 +1,39 @@
+SET search_path TO public, bm_catalog;
+SET client_min_messages TO warning;
+\set VERBOSITY terse
+
+SELECT bm25_default('tokenizer') AS tokenizer, bm25_default('model') AS model, bm25_default('style') AS style, bm25_default('b') AS b, bm25_default('k1') AS k1;
+
+-- settings are the defaults of arguments left out
+SET pg_bestmatch.default_tokenizer = 'ws';
+SET pg_bestmatch.default_model = 'none';
+SET pg_bestmatch.default_b = 0.5;
+SET pg_bestmatch.default_k1 = 2;
+SET pg_bestmatch.default_style = 'pgvector';
+SELECT bm25_default('tokenizer') AS tokenizer, bm25_default('model') AS model, bm25_default('style') AS style, bm25_default('b') AS b, bm25_default('k1') AS k1;
+
+CREATE TABLE docs (id INT PRIMARY KEY, passage TEXT, vec TEXT);
+INSERT INTO docs VALUES (1, 'apple pie'), (2, 'cherry pie pie');
+SELECT bm25_create('docs', 'passage', 'docs_bm25');
+SELECT bm25_create('docs', 'passage', 'docs_given', tokenizer => 'ws', model => 'none', b => 0.25, k1 => 1);
+SELECT matrelid, tokenizer, model, b, k1 FROM pg_bm25 ORDER BY matrelid::TEXT;
+SELECT bm25_attach('docs_bm25', 'docs', 'passage', 'vec');
+SELECT style FROM pg_bm25_columns;
+SELECT bm25_reembed('docs_bm25', 'vec');
+SELECT count(*) FILTER (WHERE vec IS DISTINCT FROM bm25_document_to_svector('docs_bm25', passage, 'pgvector')) AS stale FROM docs;
+
+-- statistics keep the values they were created with
+RESET pg_bestmatch.default_tokenizer;
+RESET pg_bestmatch.default_model;
+RESET pg_bestmatch.default_b;
+RESET pg_bestmatch.default_k1;
+RESET pg_bestmatch.default_style;
+SELECT matrelid, tokenizer, model, b, k1 FROM pg_bm25 ORDER BY matrelid::TEXT;
+SELECT bm25_default('tokenizer') AS tokenizer, bm25_default('model') AS model, bm25_default('style') AS style, bm25_default('b') AS b, bm25_default('k1') AS k1;
+
+-- failures
+SELECT bm25_default('idf');
+
+SELECT bm25_drop('docs_bm25');
+SELECT bm25_drop('docs_given');
+DROP TABLE docs;
@@ -0,0 +1,59 @@
+SET search_path TO public, bm_catalog;
+SET client_min_messages TO warning;
+\set VERBOSITY terse
+
+CREATE TABLE docs (id INT PRIMARY KEY, passage TEXT);
+INSERT INTO docs VALUES (1, 'apple pie'), (2, 'cherry pie pie'), (3, 'apple tart');
+SELECT bm25_create('docs', 'passage', 'docs_bm25', tokenizer => 'ws', model => 'none', b => 0.5);
+SELECT bm25_create('docs', 'passage', 'docs_hashed', tokenizer => 'ws', model => 'none', vocabulary => 'hashed', hash_bits => 20);
+CREATE TABLE exports AS SELECT 'corpus' AS name, bm25_export('docs_bm25') AS data
+    UNION ALL SELECT 'hashed', bm25_export('docs_hashed');
+
+SELECT data - 'terms' - 'checksum' AS export FROM exports WHERE name = 'corpus';
+SELECT jsonb_array_length(data->'terms') AS terms, length(data->>'checksum') AS checksum FROM exports;
+
+-- an import without a table is a fixed corpus with the same statistics and export
+SELECT bm25_import('docs_copy', data) FROM exports WHERE name = 'corpus';
+SELECT bm25_import('docs_hashed_copy', data) FROM exports WHERE name = 'hashed';
+SELECT token, id, df, cf FROM bm25_vocabulary('docs_copy');
+SELECT docs, words, dims, vocabulary, b, k1, idf FROM bm25_stats('docs_copy');
+SELECT bm25_export('docs_copy') = data AS same FROM exports WHERE name = 'corpus';
+SELECT bm25_export('docs_hashed_copy') = data AS same FROM exports WHERE name = 'hashed';
+SELECT attrelid, generation FROM pg_bm25 WHERE matrelid = 'docs_copy'::regclass;
+
+-- an import with a table is rebuilt from it by refreshes
+INSERT INTO docs VALUES (4, 'plum pie');
+SELECT bm25_import('docs_linked', data, 'docs', 'passage') FROM exports WHERE name = 'corpus';
+SELECT docs FROM bm25_stats('docs_linked');
+SELECT bm25_refresh('docs_linked');
+SELECT docs, words FROM bm25_stats('docs_linked');
+
+-- failures
+SELECT bm25_export('docs_bm25', 'pinecone');
+SELECT bm25_import('docs_bad', jsonb_set(data, '{terms,0,df}', '7')) FROM exports WHERE name = 'corpus';
+SELECT bm25_import('docs_bad', jsonb_set(data, '{docs}', '30')) FROM exports WHERE name = 'corpus';
+SELECT bm25_import('docs_bad', jsonb_set(data, '{hash_seed}', '1')) FROM exports WHERE name = 'hashed';
+SELECT bm25_import('docs_bad', jsonb_set(data, '{b}', '0.75')) FROM exports WHERE name = 'corpus';
+-- documents with a valid checksum are validated too
+SELECT bm25_import('docs_bad', d || jsonb_build_object('checksum', bm25_checksum(d)))
+    FROM exports, jsonb_set(data, '{hash_bits}', '31') d WHERE name = 'hashed';
+SELECT bm25_import('docs_bad', d || jsonb_build_object('checksum', bm25_checksum(d)))
+    FROM exports, jsonb_set(data, '{dims}', '2') d WHERE name = 'corpus';
+SELECT bm25_import('docs_bad', d || jsonb_build_object('checksum', bm25_checksum(d)))
+    FROM exports, jsonb_set(data, '{dims}', '1024') d WHERE name = 'hashed';
+SELECT bm25_import('docs_bad', d || jsonb_build_object('checksum', bm25_checksum(d)))
+    FROM exports, jsonb_set(data, '{terms,1,id}', '0') d WHERE name = 'corpus';
+SELECT bm25_import('docs_bad', d || jsonb_build_object('checksum', bm25_checksum(d)))
+    FROM exports, jsonb_set(data, '{terms,1,token}', data->'terms'->0->'token') d WHERE name = 'corpus';
+SELECT bm25_import('docs_bad', jsonb_set(data, '{version}', '2')) FROM exports WHERE name = 'corpus';
+SELECT bm25_import('docs_bad', data - 'format') FROM exports WHERE name = 'corpus';
+SELECT bm25_import('docs_bad', data, 'docs', 'missing') FROM exports WHERE name = 'corpus';
+SELECT count(*) FROM pg_class WHERE relname LIKE 'docs_bad%';
+
+SELECT bm25_drop('docs_bm25');
+SELECT bm25_drop('docs_hashed');
+SELECT bm25_drop('docs_copy');
+SELECT bm25_drop('docs_hashed_copy');
+SELECT bm25_drop('docs_linked');
+DROP TABLE exports;
+DROP TABLE docs;
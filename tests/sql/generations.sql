@@ -0,0 +1,54 @@
+SET search_path TO public, bm_catalog;
+SET client_min_messages TO warning;
+\set VERBOSITY terse
+
+CREATE TABLE docs (id INT PRIMARY KEY, passage TEXT);
+INSERT INTO docs VALUES (1, 'apple pie'), (2, 'cherry pie pie');
+SELECT bm25_create('docs', 'passage', 'docs_bm25', tokenizer => 'ws', model => 'none', b => 0.5, k1 => 1.5);
+
+-- each generation keeps its parameters, and changing them starts a new one with the same counts
+SELECT bm25_pin('docs_bm25', 1);
+SELECT bm25_alter('docs_bm25', b => 0.75, idf => 'lucene');
+SELECT bm25_alter('docs_bm25', b => 0.75);
+SELECT bm25_refresh('docs_bm25');
+SELECT bm25_alter('docs_bm25', k1 => 2);
+SELECT generation, docs, words, dims, b, k1, idf, current, pinned FROM bm25_generations('docs_bm25');
+
+-- generations older than the previous one are dropped unless pinned
+INSERT INTO docs VALUES (3, 'apple tart');
+SELECT bm25_refresh('docs_bm25');
+SELECT bm25_refresh('docs_bm25');
+SELECT generation, docs, current, pinned FROM bm25_generations('docs_bm25');
+SELECT bm25_pin('docs_bm25', 1, pinned => false);
+SELECT bm25_gc('docs_bm25');
+SELECT generation, docs, current, pinned FROM bm25_generations('docs_bm25');
+SELECT count(*) FROM pg_class WHERE relname LIKE 'docs_bm25_g%' AND relkind = 'r';
+
+-- a column embedded with a generation retains it
+ALTER TABLE docs ADD COLUMN vec TEXT;
+SELECT bm25_reembed('docs_bm25', 'vec', 'pgvector');
+SELECT bm25_refresh('docs_bm25');
+SELECT bm25_refresh('docs_bm25');
+SELECT generation, current, columns FROM bm25_generations('docs_bm25');
+
+-- explanations of an earlier generation
+SELECT count(*) > 0 AS explained FROM bm25_explain('docs_bm25', 'apple pie', 'apple pie', 'pgvector,normalize=l2', generation => 6);
+
+-- columns keep the generation they were embedded with when b or k1 change, and move with idf
+SELECT bm25_reembed('docs_bm25', 'vec', 'pgvector');
+SELECT bm25_alter('docs_bm25', idf => 'bm25');
+SELECT bm25_alter('docs_bm25', b => 0.5);
+SELECT generation, b, idf, current, columns FROM bm25_generations('docs_bm25');
+SELECT bm25_alter('docs_bm25', k1 => 1, reembed => 'vec', style => 'pgvector');
+SELECT generation, b, k1, current, columns FROM bm25_generations('docs_bm25');
+
+-- failures
+SELECT bm25_query_to_svector('docs_bm25', 'apple', generation => 1);
+SELECT * FROM bm25_explain('docs_bm25', 'apple', 'apple', generation => 1);
+SELECT bm25_document_to_svector('docs_bm25', 'apple', generation => 1);
+SELECT bm25_alter('docs_bm25', b => 2);
+SELECT bm25_alter('docs_bm25', idf => 'okapi');
+
+SELECT bm25_drop('docs_bm25');
+SELECT count(*) FROM pg_bm25_generations;
+DROP TABLE docs;
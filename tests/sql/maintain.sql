@@ -0,0 +1,61 @@
+SET search_path TO public, bm_catalog;
+SET client_min_messages TO warning;
+\set VERBOSITY terse
+
+-- waits until the statistics collector has counted `n` changes of a table
+CREATE FUNCTION wait_for_changes(tab regclass, n BIGINT) RETURNS BOOLEAN AS $$
+BEGIN
+    FOR i IN 1..200 LOOP
+        PERFORM pg_stat_clear_snapshot();
+        IF bm_catalog.bm25_table_changes(tab) = n THEN
+            RETURN true;
+        END IF;
+        PERFORM pg_sleep(0.05);
+    END LOOP;
+    RETURN false;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TABLE docs (id INT PRIMARY KEY, passage TEXT);
+INSERT INTO docs VALUES (1, 'apple pie'), (2, 'cherry pie pie'), (3, 'apple tart'), (4, 'plum');
+-- pending counts of the backend are reported while it is idle
+SELECT pg_sleep(1.1);
+SELECT wait_for_changes('docs', 4);
+SELECT bm25_create('docs', 'passage', 'docs_bm25', tokenizer => 'ws', model => 'none');
+SELECT matrelid, docs, generation, changes, due, pending_columns, maintained_at IS NULL AS never_maintained FROM pg_bm25_status;
+
+-- changes make a refresh due
+INSERT INTO docs VALUES (5, 'pear'), (6, 'pear pie');
+SELECT pg_sleep(1.1);
+SELECT wait_for_changes('docs', 6);
+SELECT matrelid, changes, due FROM pg_bm25_status;
+SELECT bm25_maintain(threshold => 0);
+SELECT matrelid, generation, changes, maintained_at IS NOT NULL AS maintained, maintenance_error FROM pg_bm25_status;
+SELECT bm25_maintain();
+SELECT matrelid, docs, generation, changes, due, maintenance_error FROM pg_bm25_status;
+
+-- a reset of the counts restarts them below the baseline
+SELECT pg_stat_reset_single_table_counters('docs'::regclass);
+SELECT wait_for_changes('docs', 0);
+SELECT matrelid, changes, due FROM pg_bm25_status;
+SELECT baseline_changes FROM pg_bm25;
+SELECT bm25_maintain();
+SELECT baseline_changes FROM pg_bm25;
+INSERT INTO docs VALUES (7, 'fig');
+SELECT pg_sleep(1.1);
+SELECT wait_for_changes('docs', 1);
+SELECT matrelid, changes, due FROM pg_bm25_status;
+
+-- failures are recorded and don't stop maintenance
+SELECT bm25_create('docs', 'passage', 'docs_broken', tokenizer => 'ws', model => 'none');
+UPDATE pg_bm25 SET vocabulary = 'unknown', baseline_changes = -100 WHERE matrelid = 'docs_broken'::regclass;
+SELECT bm25_maintain();
+SELECT matrelid, generation, maintenance_error FROM pg_bm25_status ORDER BY matrelid::TEXT;
+
+SELECT bm25_drop('docs_bm25');
+DELETE FROM pg_bm25 WHERE matrelid = 'docs_broken'::regclass;
+DELETE FROM pg_bm25_generations WHERE matrelid = 'docs_broken'::regclass;
+DROP VIEW docs_broken;
+DROP TABLE docs_broken_g1;
+DROP TABLE docs;
+DROP FUNCTION wait_for_changes(regclass, BIGINT);
@@ -0,0 +1,53 @@
+SET search_path TO public, bm_catalog;
+SET client_min_messages TO warning;
+\set VERBOSITY terse
+
+-- names that need quoting
+CREATE TABLE "Doc Store" (id INT PRIMARY KEY, "Body Text" TEXT);
+INSERT INTO "Doc Store" VALUES (1, 'apple pie'), (2, 'cherry pie pie'), (3, NULL);
+SELECT bm25_create('"Doc Store"', 'Body Text', '"Doc Stats"', tokenizer => 'ws', model => 'none');
+SELECT matrelid, genrelid, indexrelid::regclass, docs, words, dims, generation FROM pg_bm25;
+SELECT * FROM "Doc Stats" ORDER BY id;
+
+INSERT INTO "Doc Store" VALUES (4, 'apple tart');
+SELECT bm25_refresh('"Doc Stats"');
+SELECT matrelid, genrelid, indexrelid::regclass, docs, words, dims, generation FROM pg_bm25;
+SELECT * FROM "Doc Stats" ORDER BY id;
+SELECT generation, docs, words, dims, current, pinned FROM bm25_generations('"Doc Stats"') ORDER BY generation;
+
+-- generations stay next to the view, whatever the search path
+CREATE SCHEMA "Search Data";
+CREATE TABLE "Search Data".docs (id INT PRIMARY KEY, body TEXT);
+INSERT INTO "Search Data".docs VALUES (1, 'pear'), (2, 'pear plum');
+SELECT bm25_create('"Search Data".docs', 'body', '"Search Data"."Stats"', tokenizer => 'ws', model => 'none');
+SELECT bm25_refresh('"Search Data"."Stats"');
+SELECT generation, genrelid, bm25_index_of(genrelid)::regclass FROM pg_bm25_generations WHERE matrelid = '"Search Data"."Stats"'::regclass ORDER BY generation;
+SELECT * FROM "Search Data"."Stats" ORDER BY id;
+
+-- refreshes and readers of the current generation don't wait for each other
+SELECT dblink_connect('refresh', format('dbname=%s port=%s host=%s', current_database(), current_setting('port'),
+    coalesce(host(inet_server_addr()), split_part(current_setting('unix_socket_directories'), ',', 1))));
+SELECT dblink_exec('refresh', 'BEGIN');
+SELECT * FROM dblink('refresh', $$SELECT bm_catalog.bm25_refresh('"Doc Stats"')::TEXT$$) AS t(refreshed TEXT);
+SET lock_timeout = '2s';
+SELECT bm25_query_to_svector('"Doc Stats"', 'apple', 'pgvector') IS NOT NULL AS query;
+SELECT bm25_document_to_svector('"Doc Stats"', 'apple pie', 'pgvector') IS NOT NULL AS document;
+SELECT generation FROM pg_bm25 WHERE matrelid = '"Doc Stats"'::regclass;
+RESET lock_timeout;
+SELECT dblink_exec('refresh', 'COMMIT');
+SELECT dblink_disconnect('refresh');
+SELECT generation FROM pg_bm25 WHERE matrelid = '"Doc Stats"'::regclass;
+
+-- the previous generation is kept for readers that started before the last refresh
+SELECT generation FROM bm25_generations('"Doc Stats"') ORDER BY generation;
+
+-- failures
+SELECT bm25_refresh('"Doc Store"');
+SELECT bm25_create('"Doc Store"', 'Body', 'doc_missing', tokenizer => 'ws', model => 'none');
+SELECT bm25_import_counts('doc_counts', '"Doc Store"', 1);
+
+SELECT bm25_drop('"Doc Stats"');
+SELECT bm25_drop('"Search Data"."Stats"');
+SELECT count(*) FROM pg_bm25;
+DROP TABLE "Doc Store";
+DROP SCHEMA "Search Data" CASCADE;
@@ -0,0 +1,37 @@
+SET search_path TO public, bm_catalog;
+SET client_min_messages TO warning;
+\set VERBOSITY terse
+
+CREATE TABLE docs (id INT PRIMARY KEY, passage TEXT, vec TEXT);
+INSERT INTO docs VALUES (1, 'apple pie', NULL), (2, 'cherry pie pie', NULL);
+SELECT bm25_create('docs', 'passage', 'docs_bm25', tokenizer => 'ws', model => 'none');
+SELECT bm25_attach('docs_bm25', 'docs', 'passage', 'vec', 'pgvector');
+SELECT bm25_pin('docs_bm25', 1);
+SELECT bm25_refresh('docs_bm25');
+
+-- a restored dump has new indexes, the catalog rows of the dumped database, and views as dumped
+SET session_replication_role = replica;
+DROP INDEX docs_bm25_g1_token_idx;
+DROP INDEX docs_bm25_g2_token_idx;
+RESET session_replication_role;
+CREATE INDEX ON docs_bm25_g1(token);
+CREATE INDEX ON docs_bm25_g2(token);
+UPDATE pg_bm25 SET attnum = 9, baseline_changes = 12345;
+UPDATE pg_bm25_columns SET attnum = 9, sourcenum = 8, keynum = 7;
+CREATE OR REPLACE VIEW docs_bm25 AS SELECT * FROM docs_bm25_g1;
+SELECT count(*) FROM pg_bm25_generations g WHERE g.indexrelid IS DISTINCT FROM bm25_index_of(g.genrelid);
+
+SELECT bm25_restore();
+SELECT count(*) FROM pg_bm25_generations g WHERE g.indexrelid IS DISTINCT FROM bm25_index_of(g.genrelid);
+SELECT p.indexrelid = bm25_index_of(p.genrelid) AS current_index, attnum, baseline_changes = bm25_table_changes(attrelid) AS baseline FROM pg_bm25 p;
+SELECT attnum, sourcenum, keynum FROM pg_bm25_columns;
+SELECT pg_get_viewdef('docs_bm25');
+SELECT count(*) FROM docs_bm25;
+SELECT bm25_document_to_svector('docs_bm25', 'apple', 'pgvector') IS NOT NULL AS usable;
+SELECT bm25_query_to_svector('docs_bm25', 'apple', 'pgvector', generation => 1) IS NOT NULL AS usable;
+
+-- nothing to restore
+SELECT bm25_drop('docs_bm25');
+SELECT bm25_restore();
+
+DROP TABLE docs;
@@ -0,0 +1,43 @@
+SET search_path TO public, bm_catalog;
+SET client_min_messages TO warning;
+\set VERBOSITY terse
+
+-- 60000 distinct tokens, more than fit in the smallest maintenance_work_mem
+CREATE TABLE docs (id INT PRIMARY KEY, passage TEXT);
+INSERT INTO docs SELECT i, (SELECT string_agg('w' || ((i * 12 + j) % 60000), ' ') FROM generate_series(1, 12) j) FROM generate_series(1, 10000) i;
+INSERT INTO docs VALUES (10001, ''), (10002, NULL);
+CREATE TABLE expected AS
+    SELECT token, count(*) AS how_many_tokens, count(DISTINCT id) AS token_in_how_many_inputs
+    FROM docs, unnest(tokenize(passage, 'ws', 'none')) token GROUP BY token;
+
+-- counts spilled to temporary files and merged are those counted in memory
+SET maintenance_work_mem = '1MB';
+CREATE TABLE spilled AS SELECT * FROM bm25_count_terms('docs'::regclass, 'passage', 'ws', 'none', 'corpus', 0, 0);
+CREATE TABLE spilled_hashed AS SELECT * FROM bm25_count_terms('docs'::regclass, 'passage', 'ws', 'none', 'hashed', 16, 0);
+RESET maintenance_work_mem;
+SELECT count(*) AS tokens, sum(how_many_tokens) AS words FROM spilled;
+(SELECT token, how_many_tokens, token_in_how_many_inputs FROM spilled EXCEPT SELECT * FROM expected)
+UNION ALL
+(SELECT * FROM expected EXCEPT SELECT token, how_many_tokens, token_in_how_many_inputs FROM spilled);
+SELECT sum(how_many_tokens) AS words, max(token_in_how_many_inputs) <= 10000 AS bounded FROM spilled_hashed;
+(SELECT * FROM spilled_hashed EXCEPT SELECT * FROM bm25_count_terms('docs'::regclass, 'passage', 'ws', 'none', 'hashed', 16, 0))
+UNION ALL
+(SELECT * FROM bm25_count_terms('docs'::regclass, 'passage', 'ws', 'none', 'hashed', 16, 0) EXCEPT SELECT * FROM spilled_hashed);
+SELECT count(*) AS left_behind FROM pg_ls_tmpdir() WHERE name LIKE '%.bm25_%';
+
+-- statistics built with spilled counts
+SET maintenance_work_mem = '1MB';
+SELECT bm25_create('docs', 'passage', 'docs_bm25', tokenizer => 'ws', model => 'none');
+RESET maintenance_work_mem;
+SELECT docs, words, dims FROM bm25_stats('docs_bm25');
+SELECT count(*) FROM docs_bm25 v JOIN expected e ON e.token = v.token
+    WHERE (v.how_many_tokens, v.token_in_how_many_inputs) IS DISTINCT FROM (e.how_many_tokens, e.token_in_how_many_inputs);
+
+-- failures
+SELECT * FROM bm25_count_terms('docs'::regclass, 'missing', 'ws', 'none', 'corpus', 0, 0);
+
+SELECT bm25_drop('docs_bm25');
+DROP TABLE spilled_hashed;
+DROP TABLE spilled;
+DROP TABLE expected;
+DROP TABLE docs;
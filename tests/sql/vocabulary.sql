@@ -0,0 +1,35 @@
+SET search_path TO public, bm_catalog;
+SET client_min_messages TO warning;
+\set VERBOSITY terse
+
+CREATE TABLE docs (id INT PRIMARY KEY, passage TEXT);
+INSERT INTO docs VALUES (1, 'apple pie'), (2, 'cherry pie pie'), (3, 'apple tart');
+SELECT bm25_create('docs', 'passage', 'docs_bm25', tokenizer => 'ws', model => 'none');
+SELECT bm25_create('docs', 'passage', 'docs_hashed', tokenizer => 'ws', model => 'none', vocabulary => 'hashed', hash_bits => 20);
+
+SELECT token, id, df, cf, round(idf::NUMERIC, 4) AS idf FROM bm25_vocabulary('docs_bm25');
+SELECT token, id, df, cf FROM bm25_top_terms('docs_bm25', 2);
+SELECT docs, words, avgdl, vocabulary_size, dims, vocabulary, tokenizer, model, b, k1, idf FROM bm25_stats('docs_bm25');
+SELECT docs, words, vocabulary_size, dims, vocabulary FROM bm25_stats('docs_hashed');
+
+-- a row for each distinct token of the text, in order
+SELECT token, id, df, cf, round(idf::NUMERIC, 4) AS idf FROM bm25_term_info('docs_bm25', 'pie apple pie banana');
+SELECT token, id IS NOT NULL AS has_id, df, cf FROM bm25_term_info('docs_hashed', 'pie apple pie banana');
+SELECT count(*) FROM bm25_term_info('docs_bm25', '');
+
+-- statistics of an empty table have no average length
+CREATE TABLE empty (passage TEXT);
+SELECT bm25_create('empty', 'passage', 'empty_bm25', tokenizer => 'ws', model => 'none');
+SELECT docs, words, avgdl, vocabulary_size FROM bm25_stats('empty_bm25');
+SELECT bm25_drop('empty_bm25');
+DROP TABLE empty;
+
+-- failures
+SELECT * FROM bm25_vocabulary('docs');
+SELECT * FROM bm25_top_terms('docs');
+SELECT * FROM bm25_term_info('docs', 'pie');
+SELECT * FROM bm25_stats('docs');
+
+SELECT bm25_drop('docs_bm25');
+SELECT bm25_drop('docs_hashed');
+DROP TABLE docs;